@@ -0,0 +1,128 @@
+//! Compares drawing floors as one `IsoSprite` per tile with batching them into
+//! `ChunkMesh`es, on a generated level. Runs headless, so frame times only
+//! cover the main world; extraction is measured by a pass mirroring what
+//! `isosprite_extract` does per sprite.
+//!
+//! Run with `cargo bench --bench floors`.
+
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bevy::hierarchy::despawn_with_children_recursive;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use undercity::map::gen::generate_level;
+use undercity::map::mesh::{ChunkMesh, FloorMesh};
+use undercity::map::Map;
+use undercity::menu::GameState;
+use undercity::{world_to_iso, GamePlugin, HeadlessPlugin, IsoSprite, LevelInfo};
+
+const level: usize = 1;
+const seed: u64 = 1;
+const frames: u32 = 300;
+
+struct Results {
+	sprites: usize,
+	floorMeshes: usize,
+	frame: Duration,
+	extract: Duration,
+}
+
+fn main() {
+	for (name, spriteFloors) in [("chunk meshes", false), ("sprite per tile", true)] {
+		let res = run(spriteFloors);
+		println!(
+			"{name:>16}: {:>6} sprites extracted, {:>4} floor meshes, \
+			 {:>8.3?} per frame, {:>8.3?} extracting",
+			res.sprites, res.floorMeshes, res.frame, res.extract
+		);
+	}
+}
+
+fn run(spriteFloors: bool) -> Results {
+	let mut app = App::new();
+	app.add_plugins((MinimalPlugins, HeadlessPlugin, GamePlugin));
+	app.finish();
+	app.cleanup();
+	app.update();
+
+	let assets = app.world.resource::<AssetServer>().clone();
+	let map = generate_level(&assets, level, seed).unwrap();
+	undercity::load_level(&mut app.world, LevelInfo { level, seed }, map);
+	app.world.resource_mut::<NextState<GameState>>().set(GameState::Playing);
+	wait_for_meshes(&mut app);
+
+	if spriteFloors {
+		// back to how floors were drawn before chunk meshes
+		let mut chunkMeshes = app.world.query_filtered::<Entity, With<ChunkMesh>>();
+		for ent in chunkMeshes.iter(&app.world).collect::<Vec<_>>() {
+			despawn_with_children_recursive(&mut app.world, ent);
+		}
+		let map = app.world.resource::<Map>();
+		let floors: Vec<_> = map
+			.used_tiles()
+			.tiles()
+			.filter(|&pos| !map[pos].background.is_empty())
+			.map(|pos| map[pos].background.into_bundle(pos.as_vec2(), &assets).0)
+			.collect();
+		app.world.spawn_batch(floors);
+		app.update();
+	}
+
+	let start = Instant::now();
+	for _ in 0 .. frames {
+		app.update();
+	}
+	let frame = start.elapsed() / frames;
+
+	let mut sprites = 0;
+	let start = Instant::now();
+	for _ in 0 .. frames {
+		sprites = extract(&mut app.world);
+	}
+	let extract = start.elapsed() / frames;
+
+	let mut floorMeshes = app.world.query::<&FloorMesh>();
+	Results {
+		sprites,
+		floorMeshes: floorMeshes.iter(&app.world).count(),
+		frame,
+		extract,
+	}
+}
+
+/// Updates until every chunk has its floor meshes, which needs the floor
+/// textures to be loaded.
+fn wait_for_meshes(app: &mut App) {
+	let deadline = Instant::now() + Duration::from_secs(30);
+	loop {
+		app.update();
+		let chunks = app.world.resource::<Map>().chunks.len();
+		let mut chunkMeshes = app.world.query::<&ChunkMesh>();
+		if chunkMeshes.iter(&app.world).count() == chunks {
+			return;
+		}
+		assert!(Instant::now() < deadline, "floor textures didn't load");
+		std::thread::sleep(Duration::from_millis(10));
+	}
+}
+
+/// Does the per-sprite work of `isosprite_extract`, returning the number of
+/// sprites extracted.
+fn extract(world: &mut World) -> usize {
+	let mut query = world.query::<(&GlobalTransform, &IsoSprite)>();
+	let extracted: Vec<_> = query
+		.iter(world)
+		.map(|(transform, sprite)| {
+			let mut affine = transform.affine();
+			let pos = affine.translation.xy();
+			let mut isoPos = world_to_iso(pos);
+			isoPos.z = sprite.layer.depth(sprite.sort_point(pos)) + affine.translation.z;
+			affine.translation = isoPos.into();
+			(affine, sprite.rect, sprite.color)
+		})
+		.collect();
+	black_box(extracted).len()
+}
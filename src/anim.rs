@@ -0,0 +1,235 @@
+//! Frame-by-frame [`IsoSprite`] animation. Clips are RON files listing the
+//! atlas indices of their frames, see `assets/animations`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::map::{atlas_rect, load_ron_blocking};
+use crate::{AResult, IsoSprite, IsoSpriteBundle, RenderLayer};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum PlayMode {
+	#[default]
+	Loop,
+	/// Stops on the last frame and sends [`AnimationFinished`].
+	Once,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct AnimationClip {
+	/// Texture the frames are taken from when spawning an
+	/// [effect](spawn_effect). Other animations use the sprite's texture.
+	pub texture: String,
+	/// Width of the texture atlas in tiles.
+	pub columns: u32,
+	/// Atlas indices of each frame.
+	pub frames: Vec<u32>,
+	/// Seconds each frame is shown for.
+	pub frameTime: f32,
+	pub mode: PlayMode,
+
+	/// Asset path the clip was loaded from.
+	#[serde(skip)]
+	pub path: String,
+}
+
+impl Default for AnimationClip {
+	fn default() -> Self {
+		Self {
+			texture: "tiles/misc.png".into(),
+			columns: 16,
+			frames: vec![],
+			frameTime: 0.1,
+			mode: default(),
+			path: default(),
+		}
+	}
+}
+
+impl AnimationClip {
+	pub fn load_blocking(assets: &AssetServer, path: &str) -> AResult<Self> {
+		let mut clip: Self = load_ron_blocking(assets, path)?;
+		clip.path = path.into();
+		Ok(clip)
+	}
+
+	pub fn frame_rect(&self, frame: usize) -> Option<Rect> {
+		let &index = self.frames.get(frame)?;
+		Some(atlas_rect(index, self.columns))
+	}
+}
+
+/// Clips loaded so far, by asset path.
+#[derive(Default, Resource)]
+pub struct AnimationClips(HashMap<String, Arc<AnimationClip>>);
+
+impl AnimationClips {
+	/// Returns the clip at `path`, loading it on first use. Clips that fail
+	/// to load are logged and replaced by an empty clip, so a broken file
+	/// only costs its animation.
+	pub fn get(&mut self, assets: &AssetServer, path: &str) -> Arc<AnimationClip> {
+		self.0
+			.entry(path.into())
+			.or_insert_with(|| {
+				let clip = AnimationClip::load_blocking(assets, path).unwrap_or_else(|err| {
+					error!("failed to load animation {path:?}: {err:#}");
+					AnimationClip {
+						path: path.into(),
+						..default()
+					}
+				});
+				Arc::new(clip)
+			})
+			.clone()
+	}
+}
+
+/// Plays an [`AnimationClip`] on the entity's [`IsoSprite`].
+#[derive(Clone, Debug, Component)]
+pub struct Animation {
+	pub clip: Arc<AnimationClip>,
+	/// Paused animations hold their current frame.
+	pub paused: bool,
+	frame: usize,
+	timer: Timer,
+	finished: bool,
+}
+
+impl Animation {
+	pub fn new(clip: Arc<AnimationClip>) -> Self {
+		let timer = Timer::from_seconds(clip.frameTime, TimerMode::Repeating);
+		Self {
+			clip,
+			paused: false,
+			frame: 0,
+			timer,
+			finished: false,
+		}
+	}
+
+	/// Switches to `clip`, unless it's already playing.
+	pub fn play(&mut self, clip: Arc<AnimationClip>) {
+		if !Arc::ptr_eq(&self.clip, &clip) {
+			*self = Self {
+				paused: self.paused,
+				..Self::new(clip)
+			};
+		}
+	}
+
+	/// Jumps back to the first frame.
+	pub fn restart(&mut self) {
+		self.frame = 0;
+		self.timer.reset();
+		self.finished = false;
+	}
+
+	pub fn frame(&self) -> usize {
+		self.frame
+	}
+
+	/// Whether a [one-shot](PlayMode::Once) clip reached its last frame.
+	pub fn is_finished(&self) -> bool {
+		self.finished
+	}
+}
+
+/// Sent once when a [one-shot](PlayMode::Once) animation reaches its last
+/// frame.
+#[derive(Clone, Debug, Event)]
+pub struct AnimationFinished {
+	pub entity: Entity,
+	pub clip: Arc<AnimationClip>,
+}
+
+/// Marks entities that only exist to play an animation, and are despawned
+/// once it finishes.
+#[derive(Clone, Copy, Debug, Default, Component)]
+pub struct Effect;
+
+/// Spawns a one-off animation at `pos` (in world space), drawn over every
+/// object, e.g. an explosion.
+pub fn spawn_effect(
+	cmd: &mut Commands,
+	assets: &AssetServer,
+	clip: Arc<AnimationClip>,
+	pos: Vec2,
+) -> Entity {
+	cmd.spawn((
+		Effect,
+		IsoSpriteBundle {
+			texture: assets.load(clip.texture.as_str()),
+			sprite: IsoSprite {
+				rect: clip.frame_rect(0).unwrap_or_default(),
+				layer: RenderLayer::Overhead,
+				..default()
+			},
+			transform: Transform::from_translation((pos, 0.0).into()).into(),
+			..default()
+		},
+		Animation::new(clip),
+	))
+	.id()
+}
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.init_resource::<AnimationClips>();
+	app.add_event::<AnimationFinished>();
+	// after gameplay systems, so clips they start show their first frame right away
+	app.add_systems(PostUpdate, (animate, despawn_effects).chain());
+}
+
+fn animate(
+	time: Res<Time>,
+	mut query: Query<(Entity, &mut Animation, &mut IsoSprite)>,
+	mut finished: EventWriter<AnimationFinished>,
+) {
+	for (entity, mut anim, mut sprite) in &mut query {
+		let len = anim.clip.frames.len();
+		if len == 0 {
+			continue;
+		}
+
+		if !anim.paused && !anim.finished {
+			let anim = &mut *anim;
+			anim.timer.tick(time.delta());
+			let mut frame = anim.frame + anim.timer.times_finished_this_tick() as usize;
+			if frame >= len {
+				match anim.clip.mode {
+					PlayMode::Loop => frame %= len,
+					PlayMode::Once => {
+						frame = len - 1;
+						anim.finished = true;
+						finished.send(AnimationFinished {
+							entity,
+							clip: anim.clip.clone(),
+						});
+					},
+				}
+			}
+			anim.frame = frame;
+		}
+
+		let rect = anim.clip.frame_rect(anim.frame).unwrap();
+		if sprite.rect != rect {
+			sprite.rect = rect;
+		}
+	}
+}
+
+fn despawn_effects(
+	mut cmd: Commands,
+	mut finished: EventReader<AnimationFinished>,
+	effects: Query<(), With<Effect>>,
+) {
+	for ev in &mut finished {
+		if effects.contains(ev.entity) {
+			cmd.entity(ev.entity).despawn_recursive();
+		}
+	}
+}
@@ -0,0 +1,263 @@
+//! Sound effects and ambient tracks. Effects are described by `sounds.ron`
+//! and requested with [`PlaySound`]; positional ones fade with distance from
+//! the player. Each level theme names an ambient track looped in the
+//! background. Everything is scaled by [`Settings::volume`].
+
+use std::collections::HashMap;
+
+use bevy::audio::{AudioSinkPlayback, Volume};
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use serde::Deserialize;
+
+use crate::entities::player::Player;
+use crate::interact::InteractSet;
+use crate::map::gen::LevelDef;
+use crate::map::{load_ron_blocking, tileRadius, FloorType, Map, TilePos, TileType, Tileset};
+use crate::settings::Settings;
+use crate::LevelInfo;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum SoundKind {
+	DoorOpen,
+	DoorClose,
+	ShrineHeal,
+	ShrineDamage,
+	Teleport,
+	Hit,
+}
+
+/// Plays a sound effect at `pos` (in world space), or at full volume if
+/// `None`.
+#[derive(Clone, Copy, Debug, Event)]
+pub struct PlaySound {
+	pub kind: SoundKind,
+	pub pos: Option<Vec2>,
+}
+
+impl PlaySound {
+	pub fn at(kind: SoundKind, pos: Vec2) -> Self {
+		Self {
+			kind,
+			pos: Some(pos),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SoundDef {
+	/// Asset paths of variations, picked from at random.
+	pub paths: Vec<String>,
+	#[serde(default = "SoundDef::full_volume")]
+	pub volume: f32,
+	/// Playback speed varies randomly by up to this much either way.
+	#[serde(default)]
+	pub pitchVariance: f32,
+}
+
+impl SoundDef {
+	fn full_volume() -> f32 {
+		1.0
+	}
+}
+
+/// Contents of `sounds.ron`.
+#[derive(Clone, Debug, Resource, Deserialize)]
+#[serde(default)]
+pub struct SoundBank {
+	/// Positional sounds play at full volume up to `near` tiles from the
+	/// player, and fade out until `far` tiles.
+	pub near: f32,
+	pub far: f32,
+	pub effects: HashMap<SoundKind, SoundDef>,
+	/// Tiles walked per footstep.
+	pub stride: f32,
+	/// Footsteps on floors not listed below.
+	pub footstep: Option<SoundDef>,
+	pub floorFootsteps: HashMap<FloorType, SoundDef>,
+	/// Footsteps on [`FloorType::Tileset`] floors, by tileset.
+	pub tilesetFootsteps: HashMap<Tileset, SoundDef>,
+	pub ambientVolume: f32,
+}
+
+impl Default for SoundBank {
+	fn default() -> Self {
+		Self {
+			near: 2.0,
+			far: 14.0,
+			effects: default(),
+			stride: 1.2,
+			footstep: None,
+			floorFootsteps: default(),
+			tilesetFootsteps: default(),
+			ambientVolume: 0.5,
+		}
+	}
+}
+
+impl SoundBank {
+	/// Volume multiplier for a sound `distance` pixels away from the player.
+	fn falloff(&self, distance: f32) -> f32 {
+		let range = (self.far - self.near).max(f32::EPSILON);
+		1.0 - ((distance / tileRadius - self.near) / range).clamp(0.0, 1.0)
+	}
+
+	fn footstep(&self, floor: FloorType, tileset: Tileset) -> Option<&SoundDef> {
+		match floor {
+			FloorType::Tileset => self.tilesetFootsteps.get(&tileset),
+			_ => self.floorFootsteps.get(&floor),
+		}
+		.or(self.footstep.as_ref())
+	}
+}
+
+#[derive(Component)]
+struct AmbientTrack;
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.add_event::<PlaySound>();
+	app.add_systems(Startup, load_sounds);
+	app.add_systems(
+		Update,
+		(
+			footsteps.run_if(resource_exists::<Map>()),
+			play_sounds,
+			switch_ambient.run_if(resource_exists_and_changed::<LevelInfo>()),
+			update_ambient_volume,
+		)
+			.chain()
+			.after(InteractSet::Handle),
+	);
+}
+
+fn load_sounds(mut cmd: Commands, assets: Res<AssetServer>) {
+	let bank = load_ron_blocking(&assets, "sounds.ron").unwrap_or_else(|err| {
+		error!("playing no sound effects, could not load sounds.ron: {err:#}");
+		SoundBank::default()
+	});
+	cmd.insert_resource(bank);
+}
+
+fn play(cmd: &mut Commands, assets: &AssetServer, def: &SoundDef, volume: f32) {
+	let Some(path) = def.paths.choose(&mut thread_rng()) else {
+		return;
+	};
+	let volume = def.volume * volume;
+	if volume <= 0.0 {
+		return;
+	}
+	let variance = def.pitchVariance;
+	let speed = 1.0 + thread_rng().gen_range(-variance ..= variance);
+	cmd.spawn(AudioBundle {
+		source: assets.load(path),
+		settings: PlaybackSettings::DESPAWN
+			.with_volume(Volume::new_relative(volume))
+			.with_speed(speed),
+	});
+}
+
+fn play_sounds(
+	mut cmd: Commands,
+	mut events: EventReader<PlaySound>,
+	bank: Res<SoundBank>,
+	settings: Res<Settings>,
+	assets: Res<AssetServer>,
+	player: Query<&Transform, With<Player>>,
+) {
+	let listener = player.get_single().map_or(Vec2::ZERO, |transform| transform.translation.xy());
+	for ev in &mut events {
+		let Some(def) = bank.effects.get(&ev.kind) else {
+			continue;
+		};
+		let falloff = ev.pos.map_or(1.0, |pos| bank.falloff(pos.distance(listener)));
+		play(&mut cmd, &assets, def, settings.volume * falloff);
+	}
+}
+
+/// Plays a footstep whenever the player has walked another stride, sounding
+/// like the floor they're on.
+fn footsteps(
+	mut cmd: Commands,
+	bank: Res<SoundBank>,
+	settings: Res<Settings>,
+	assets: Res<AssetServer>,
+	map: Res<Map>,
+	player: Query<&Transform, With<Player>>,
+	mut last: Local<Option<Vec2>>,
+	mut walked: Local<f32>,
+) {
+	let Ok(transform) = player.get_single() else {
+		return;
+	};
+	let pos = transform.translation.xy();
+	let moved = last.replace(pos).map_or(0.0, |last| last.distance(pos));
+	// teleports and level changes aren't steps
+	if moved > tileRadius {
+		return;
+	}
+
+	*walked += moved;
+	if *walked < bank.stride * tileRadius {
+		return;
+	}
+	*walked = 0.0;
+
+	let tile = (pos / tileRadius).round();
+	let floor = map[TilePos::of(tile.x as _, tile.y as _)].background;
+	let TileType::Floor(floorType) = floor.ty else {
+		return;
+	};
+	if let Some(def) = bank.footstep(floorType, floor.tileset) {
+		play(&mut cmd, &assets, def, settings.volume);
+	}
+}
+
+/// Starts the ambient track of the level's theme, replacing the previous one.
+fn switch_ambient(
+	mut cmd: Commands,
+	info: Res<LevelInfo>,
+	bank: Res<SoundBank>,
+	settings: Res<Settings>,
+	assets: Res<AssetServer>,
+	tracks: Query<Entity, With<AmbientTrack>>,
+) {
+	for ent in &tracks {
+		cmd.entity(ent).despawn();
+	}
+
+	let def = LevelDef::load_blocking(&assets, info.level);
+	let path = match def.and_then(|def| def.load_theme(&assets)) {
+		Ok(theme) => theme.ambient,
+		Err(err) => {
+			error!("no ambient track, could not load theme of level {}: {err:#}", info.level);
+			None
+		},
+	};
+	let Some(path) = path else {
+		return;
+	};
+	cmd.spawn((
+		AmbientTrack,
+		AudioBundle {
+			source: assets.load(path),
+			settings: PlaybackSettings::LOOP
+				.with_volume(Volume::new_relative(bank.ambientVolume * settings.volume)),
+		},
+	));
+}
+
+fn update_ambient_volume(
+	bank: Res<SoundBank>,
+	settings: Res<Settings>,
+	tracks: Query<&AudioSink, With<AmbientTrack>>,
+) {
+	if !settings.is_changed() {
+		return;
+	}
+	for sink in &tracks {
+		sink.set_volume(bank.ambientVolume * settings.volume);
+	}
+}
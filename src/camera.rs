@@ -0,0 +1,216 @@
+//! The game camera: follows the player with some lag, zooms smoothly, shakes
+//! on [`ScreenShake`] and, in debug builds, can be detached into a free
+//! camera.
+
+use bevy::input::mouse::MouseWheel;
+use bevy::math::{vec2, Vec3Swizzles};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::entities::player::{depthRange, Player};
+use crate::feed::HistoryPanel;
+use crate::input::{Action, BindingsScreen, MoveAxis};
+use crate::map::load_ron_blocking;
+use crate::menu::GameState;
+use crate::world_to_iso;
+
+/// Tuning of the game camera, loaded from `camera.ron`.
+#[derive(Clone, Debug, Resource, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CameraSettings {
+	/// Seconds the camera takes to cover most of the distance to the player;
+	/// 0 snaps to them.
+	pub lag: f32,
+	/// Half size of the box around the screen center, in pixels, the player
+	/// can move in without the camera following.
+	pub deadZone: Vec2,
+	/// Like `lag`, for zooming.
+	pub zoomLag: f32,
+	/// Largest shake offset in pixels, at full trauma.
+	pub maxShake: f32,
+	/// Trauma lost per second.
+	pub shakeDecay: f32,
+	/// Speed of the free camera, in screen heights per second.
+	pub freeSpeed: f32,
+}
+
+impl Default for CameraSettings {
+	fn default() -> Self {
+		Self {
+			lag: 0.15,
+			deadZone: vec2(48.0, 32.0),
+			zoomLag: 0.1,
+			maxShake: 24.0,
+			shakeDecay: 1.5,
+			freeSpeed: 1.0,
+		}
+	}
+}
+
+/// State of the game camera.
+#[derive(Clone, Debug, Component)]
+pub struct CameraRig {
+	/// Where the camera looks, in screen space, before shaking.
+	pub focus: Vec2,
+	/// Projection scale the camera is zooming towards.
+	pub zoom: f32,
+	/// Amount of shake, from 0 to 1. Shake grows with the square of trauma,
+	/// so small hits stay subtle.
+	pub trauma: f32,
+	/// Whether the camera is detached from the player and moved by the
+	/// movement controls instead.
+	pub free: bool,
+}
+
+impl Default for CameraRig {
+	fn default() -> Self {
+		Self {
+			focus: Vec2::ZERO,
+			zoom: 1.0,
+			trauma: 0.0,
+			free: false,
+		}
+	}
+}
+
+/// Shakes the camera, adding `0.0 ..= 1.0` trauma.
+#[derive(Clone, Copy, Debug, Event)]
+pub struct ScreenShake(pub f32);
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.init_resource::<CameraSettings>();
+	app.add_event::<ScreenShake>();
+	app.add_systems(Startup, (load_settings, startup));
+	app.add_systems(
+		Update,
+		(
+			zoom_camera,
+			#[cfg(debug_assertions)]
+			toggle_free_camera,
+			move_camera,
+		)
+			.chain(),
+	);
+}
+
+fn load_settings(mut cmd: Commands, assets: Res<AssetServer>) {
+	let settings = load_ron_blocking(&assets, "camera.ron").unwrap_or_else(|err| {
+		warn!("using default camera settings, could not load camera.ron: {err:#}");
+		CameraSettings::default()
+	});
+	cmd.insert_resource(settings);
+}
+
+fn startup(mut cmd: Commands) {
+	cmd.spawn((
+		CameraRig::default(),
+		Camera2dBundle {
+			projection: OrthographicProjection {
+				far: depthRange,
+				..default()
+			},
+			..default()
+		},
+	));
+}
+
+/// Fraction of the way to cover this frame when closing in on a target with
+/// the given lag.
+fn smoothing(lag: f32, dt: f32) -> f32 {
+	if lag <= 0.0 {
+		1.0
+	} else {
+		1.0 - (-dt / lag).exp()
+	}
+}
+
+fn move_camera(
+	playerQuery: Query<&Transform, With<Player>>,
+	mut cameraQuery: Query<
+		(&mut Transform, &mut OrthographicProjection, &mut CameraRig),
+		Without<Player>,
+	>,
+	settings: Res<CameraSettings>,
+	moveAxis: Res<MoveAxis>,
+	time: Res<Time>,
+	mut shakes: EventReader<ScreenShake>,
+) {
+	let dt = time.delta_seconds();
+	let (mut transform, mut projection, mut rig) = cameraQuery.single_mut();
+
+	if rig.free {
+		let speed = settings.freeSpeed * projection.area.height();
+		rig.focus += moveAxis.0 * speed * dt;
+	} else {
+		let target = world_to_iso(playerQuery.single().translation.xy()).xy();
+		// only follow as far as needed to keep the player inside the dead zone
+		let offset = target - rig.focus;
+		let desired = rig.focus + offset - offset.clamp(-settings.deadZone, settings.deadZone);
+		let focus = rig.focus;
+		rig.focus = if offset.length() > projection.area.height() {
+			// cut straight to the player after spawning or teleporting, rather than panning
+			target
+		} else {
+			focus.lerp(desired, smoothing(settings.lag, dt))
+		};
+	}
+
+	for ScreenShake(amount) in &mut shakes {
+		rig.trauma += amount;
+	}
+	rig.trauma = (rig.trauma - settings.shakeDecay * dt).clamp(0.0, 1.0);
+	let t = time.elapsed_seconds();
+	let noise = vec2(
+		(t * 37.0).sin() * (t * 13.0).cos(),
+		(t * 29.0 + 1.3).sin() * (t * 17.0).cos(),
+	);
+	let shake = noise * settings.maxShake * rig.trauma.powi(2);
+
+	transform.translation = (rig.focus + shake, depthRange).into();
+
+	// zoom in log space, so zooming in and out feel the same
+	let scale = projection.scale.ln();
+	let zoom = smoothing(settings.zoomLag, dt);
+	projection.scale = (scale + (rig.zoom.ln() - scale) * zoom).exp();
+}
+
+fn zoom_camera(
+	mut query: Query<&mut CameraRig>,
+	actions: Res<Input<Action>>,
+	history: Res<HistoryPanel>,
+	bindings: Res<BindingsScreen>,
+	state: Res<State<GameState>>,
+	mut mouseWheel: EventReader<MouseWheel>,
+) {
+	const step: f32 = 0.1;
+	const min: f32 = 0.1;
+	const max: f32 = 2.5;
+
+	let mut scrollDelta = 0.0;
+	for ev in &mut mouseWheel {
+		scrollDelta += ev.y;
+	}
+	// the wheel scrolls the history instead, and menus don't zoom at all
+	if history.open || bindings.open || *state.get() != GameState::Playing {
+		return;
+	}
+
+	let add = if actions.just_pressed(Action::ZoomIn) || scrollDelta > 0.0 {
+		-step
+	} else if actions.just_pressed(Action::ZoomOut) || scrollDelta < 0.0 {
+		step
+	} else {
+		return;
+	};
+	let mut rig = query.single_mut();
+	rig.zoom = (rig.zoom.ln() + add).exp().clamp(min, max);
+}
+
+#[cfg(debug_assertions)]
+fn toggle_free_camera(actions: Res<Input<Action>>, mut query: Query<&mut CameraRig>) {
+	if actions.just_pressed(Action::FreeCamera) {
+		let mut rig = query.single_mut();
+		rig.free = !rig.free;
+	}
+}
@@ -0,0 +1,421 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+
+use crate::entities::door;
+use crate::entities::player::Cursor;
+use crate::entities::shrine;
+use crate::feed::{FeedCategory, FeedEntry, Severity};
+use crate::map::{
+	tileRadius, FloorType, Landmark, Map, MapTile, MutMap, PluckedTile, Prefab, Tile, TilePair,
+	TilePos, TileRect, TileType, Tileset, WallShape,
+};
+
+/// Where [`Editor`] exports selections to, relative to the assets directory.
+const exportPath: &str = "prefabs/export.ron";
+const maxUndo: usize = 256;
+/// `setupMap` hooks whose entities stay on their tile, rerun on painted tiles.
+const tileHooks: [fn(&mut MutMap, &mut Commands, &AssetServer); 2] =
+	[door::setup_map, shrine::setup_map];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Tool {
+	#[default]
+	Paint,
+	Fill,
+	Border,
+	Select,
+}
+
+impl Tool {
+	fn next(self) -> Self {
+		match self {
+			Self::Paint => Self::Fill,
+			Self::Fill => Self::Border,
+			Self::Border => Self::Select,
+			Self::Select => Self::Paint,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum BrushKind {
+	#[default]
+	Floor,
+	Wall,
+	DoorNS,
+	DoorEW,
+	Landmark,
+	Erase,
+}
+
+/// A reversible change to the map: every affected tile before and after.
+struct Edit(Vec<(TilePos, TilePair, TilePair)>);
+
+/// State of the in-game map editor. Toggled with F2; while enabled, clicking
+/// paints the current brush at the [`Cursor`].
+///
+/// Controls: 1-5/0 pick floor, wall, NS door, EW door, landmark or eraser;
+/// `[`/`]` cycle tilesets; `,`/`.` cycle floor types, wall shapes and
+/// landmarks; Tab cycles tools; Ctrl+Z/Ctrl+Y undo and redo; Ctrl+S exports the
+/// selection as a prefab.
+#[derive(Default, Resource)]
+pub struct Editor {
+	pub enabled: bool,
+	tool: Tool,
+	kind: BrushKind,
+	tileset: usize,
+	variant: usize,
+	dragStart: Option<TilePos>,
+	selection: Option<TileRect>,
+	undo: Vec<Edit>,
+	redo: Vec<Edit>,
+}
+
+impl Editor {
+	/// Number of variants the current brush kind can cycle through.
+	fn variants(&self) -> usize {
+		match self.kind {
+			BrushKind::Floor => FloorType::all.len(),
+			BrushKind::Wall => WallShape::all.len(),
+			BrushKind::Landmark => Landmark::all.len(),
+			BrushKind::DoorNS | BrushKind::DoorEW | BrushKind::Erase => 1,
+		}
+	}
+
+	/// Returns the tile painted by the current brush, or `None` when erasing.
+	fn brush(&self) -> Option<Tile> {
+		let variant = self.variant;
+		let ty = match self.kind {
+			BrushKind::Floor => TileType::Floor(FloorType::all[variant]),
+			BrushKind::Wall => TileType::Wall(WallShape::all[variant]),
+			BrushKind::DoorNS => TileType::DoorNS { open: false },
+			BrushKind::DoorEW => TileType::DoorEW { open: false },
+			BrushKind::Landmark => TileType::Landmark {
+				ty: Landmark::all[variant],
+				flip: false,
+			},
+			BrushKind::Erase => return None,
+		};
+		Some(Tile {
+			ty,
+			tileset: Tileset::all[self.tileset % Tileset::all.len()],
+		})
+	}
+}
+
+#[derive(Component)]
+struct EditorText;
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.init_resource::<Editor>();
+	app.add_systems(Startup, startup_gui);
+	app.add_systems(
+		Update,
+		(
+			toggle_editor,
+			(
+				pick_brush,
+				paint.after(pick_brush),
+				history.after(paint),
+				export,
+				update_gui,
+			)
+				.run_if(|editor: Res<Editor>| editor.enabled)
+				.run_if(resource_exists::<Map>()),
+		)
+			.chain(),
+	);
+}
+
+fn toggle_editor(
+	keyboard: Res<Input<KeyCode>>,
+	mut editor: ResMut<Editor>,
+	mut text: Query<&mut Visibility, With<EditorText>>,
+) {
+	if keyboard.just_pressed(KeyCode::F2) {
+		editor.enabled = !editor.enabled;
+		editor.dragStart = None;
+		*text.single_mut() = if editor.enabled {
+			Visibility::Inherited
+		} else {
+			Visibility::Hidden
+		};
+	}
+}
+
+fn pick_brush(keyboard: Res<Input<KeyCode>>, mut editor: ResMut<Editor>) {
+	let kinds = [
+		(KeyCode::Key1, BrushKind::Floor),
+		(KeyCode::Key2, BrushKind::Wall),
+		(KeyCode::Key3, BrushKind::DoorNS),
+		(KeyCode::Key4, BrushKind::DoorEW),
+		(KeyCode::Key5, BrushKind::Landmark),
+		(KeyCode::Key0, BrushKind::Erase),
+	];
+	for (key, kind) in kinds {
+		if keyboard.just_pressed(key) {
+			editor.kind = kind;
+			editor.variant = 0;
+		}
+	}
+
+	if keyboard.just_pressed(KeyCode::BracketRight) {
+		editor.tileset = (editor.tileset + 1) % Tileset::all.len();
+	}
+	if keyboard.just_pressed(KeyCode::BracketLeft) {
+		editor.tileset = (editor.tileset + Tileset::all.len() - 1) % Tileset::all.len();
+	}
+	let variants = editor.variants();
+	if keyboard.just_pressed(KeyCode::Period) {
+		editor.variant = (editor.variant + 1) % variants;
+	}
+	if keyboard.just_pressed(KeyCode::Comma) {
+		editor.variant = (editor.variant + variants - 1) % variants;
+	}
+	if keyboard.just_pressed(KeyCode::Tab) {
+		editor.tool = editor.tool.next();
+		editor.dragStart = None;
+	}
+}
+
+fn cursor_tile(cursor: &Query<&Transform, With<Cursor>>) -> TilePos {
+	let pos = (cursor.single().translation.xy() / tileRadius).round();
+	TilePos::of(pos.x as _, pos.y as _)
+}
+
+fn paint(
+	mut cmd: Commands,
+	mouse: Res<Input<MouseButton>>,
+	cursor: Query<&Transform, With<Cursor>>,
+	tileEnts: Query<(Entity, &MapTile)>,
+	pluckedEnts: Query<(Entity, &PluckedTile)>,
+	assets: Res<AssetServer>,
+	mut map: ResMut<Map>,
+	mut editor: ResMut<Editor>,
+) {
+	let pos = cursor_tile(&cursor);
+	let tool = editor.tool;
+	let rect = match tool {
+		Tool::Paint if mouse.pressed(MouseButton::Left) => {
+			if map[pos] == apply_brush(map[pos], editor.brush()) {
+				return;
+			}
+			TileRect::new(pos, pos)
+		},
+		Tool::Paint => return,
+		_ => {
+			if mouse.just_pressed(MouseButton::Left) {
+				editor.dragStart = Some(pos);
+			}
+			if !mouse.just_released(MouseButton::Left) {
+				return;
+			}
+			let Some(start) = editor.dragStart.take() else {
+				return;
+			};
+			TileRect::new(start, pos)
+		},
+	};
+
+	editor.selection = Some(rect);
+	let brush = editor.brush();
+	let edit = match (tool, brush) {
+		(Tool::Select, _) => return,
+		(Tool::Border, Some(tile)) => {
+			edit_map(&mut map, rect, |map| map.fill_border(tile, rect.min, rect.max))
+		},
+		(Tool::Fill, Some(tile)) => {
+			edit_map(&mut map, rect, |map| map.fill(tile, rect.min, rect.max))
+		},
+		(Tool::Border, None) => edit_map(&mut map, rect, |map| {
+			let inner = rect.expand(-1);
+			for pos in rect.tiles().filter(|&pos| !inner.contains(pos)) {
+				map[pos].clear();
+			}
+		}),
+		(Tool::Paint | Tool::Fill, _) => edit_map(&mut map, rect, |map| {
+			for pos in rect.tiles() {
+				map[pos] = apply_brush(map[pos], brush);
+			}
+		}),
+	};
+
+	// e.g. filling with what's already there
+	if edit.0.is_empty() {
+		return;
+	}
+	respawn_tiles(&mut cmd, &assets, &tileEnts, &pluckedEnts, &mut map, &edit);
+	editor.undo.push(edit);
+	if editor.undo.len() > maxUndo {
+		editor.undo.remove(0);
+	}
+	editor.redo.clear();
+}
+
+fn apply_brush(mut pair: TilePair, brush: Option<Tile>) -> TilePair {
+	match brush {
+		Some(tile) => pair.set(tile),
+		None => pair.clear(),
+	}
+	pair
+}
+
+/// Runs `func` on the live map, recording the tiles within `rect` before and
+/// after.
+fn edit_map(map: &mut Map, rect: TileRect, func: impl FnOnce(&mut MutMap)) -> Edit {
+	let before: Vec<_> = rect.tiles().map(|pos| (pos, map[pos])).collect();
+
+	let mut mutMap = MutMap::new(None);
+	std::mem::swap(&mut mutMap.map, map);
+	func(&mut mutMap);
+	std::mem::swap(&mut mutMap.map, map);
+
+	Edit(
+		before
+			.into_iter()
+			.map(|(pos, before)| (pos, before, map[pos]))
+			.filter(|(_, before, after)| before != after)
+			.collect(),
+	)
+}
+
+/// Replaces the entities of every tile touched by `edit`: doors and shrines
+/// are respawned through their `setupMap` hooks, everything else as static
+/// tiles.
+fn respawn_tiles(
+	cmd: &mut Commands,
+	assets: &AssetServer,
+	tiles: &Query<(Entity, &MapTile)>,
+	pluckedTiles: &Query<(Entity, &PluckedTile)>,
+	map: &mut Map,
+	edit: &Edit,
+) {
+	let positions: HashSet<_> = edit.0.iter().map(|&(pos, ..)| pos).collect();
+	let tileEnts = tiles.iter().map(|(ent, &MapTile(pos))| (ent, pos));
+	let pluckedEnts = pluckedTiles.iter().map(|(ent, &PluckedTile(pos))| (ent, pos));
+	for (ent, pos) in tileEnts.chain(pluckedEnts) {
+		if positions.contains(&pos) {
+			cmd.entity(ent).despawn_recursive();
+		}
+	}
+
+	let mut painted = MutMap::new(None);
+	for &pos in &positions {
+		painted[pos] = TilePair {
+			plucked: false,
+			..map[pos]
+		};
+	}
+	for hook in tileHooks {
+		hook(&mut painted, cmd, assets);
+	}
+	for pos in positions {
+		map[pos] = painted[pos];
+		if !map[pos].is_empty() {
+			map[pos].into_entity(pos, cmd, assets);
+		}
+	}
+}
+
+fn history(
+	mut cmd: Commands,
+	keyboard: Res<Input<KeyCode>>,
+	tileEnts: Query<(Entity, &MapTile)>,
+	pluckedEnts: Query<(Entity, &PluckedTile)>,
+	assets: Res<AssetServer>,
+	mut map: ResMut<Map>,
+	mut editor: ResMut<Editor>,
+) {
+	let ctrl = keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+	let shift = keyboard.pressed(KeyCode::ShiftLeft);
+	let undo = ctrl && !shift && keyboard.just_pressed(KeyCode::Z);
+	let redo = ctrl &&
+		(keyboard.just_pressed(KeyCode::Y) || shift && keyboard.just_pressed(KeyCode::Z));
+	if !undo && !redo {
+		return;
+	}
+
+	let editor = &mut *editor;
+	let (from, to) = if undo {
+		(&mut editor.undo, &mut editor.redo)
+	} else {
+		(&mut editor.redo, &mut editor.undo)
+	};
+	let Some(edit) = from.pop() else {
+		return;
+	};
+
+	for &(pos, before, after) in &edit.0 {
+		map[pos] = if undo { before } else { after };
+	}
+	respawn_tiles(&mut cmd, &assets, &tileEnts, &pluckedEnts, &mut map, &edit);
+	to.push(edit);
+}
+
+fn export(
+	keyboard: Res<Input<KeyCode>>,
+	assets: Res<AssetServer>,
+	map: Res<Map>,
+	editor: Res<Editor>,
+) {
+	let ctrl = keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+	if !(ctrl && keyboard.just_pressed(KeyCode::S)) {
+		return;
+	}
+
+	let rect = editor.selection.unwrap_or_else(|| map.used_tiles());
+	let entry = match Prefab::from_map(&map, rect).save_blocking(&assets, exportPath) {
+		Ok(()) => FeedEntry::new(format!("Exported {rect:?} to {exportPath}")),
+		Err(err) => {
+			FeedEntry::new(format!("Could not export prefab: {err}")).severity(Severity::Warning)
+		},
+	};
+	entry.category(FeedCategory::System).submit();
+}
+
+fn startup_gui(mut cmd: Commands, assets: Res<AssetServer>) {
+	cmd.spawn((
+		EditorText,
+		TextBundle {
+			text: Text::from_section(
+				"",
+				TextStyle {
+					font: assets.load("fonts/RedHatDisplay.ttf"),
+					font_size: 24.0,
+					color: Color::WHITE,
+				},
+			),
+			style: Style {
+				position_type: PositionType::Absolute,
+				bottom: Val::Px(10.0),
+				right: Val::Px(10.0),
+				..default()
+			},
+			background_color: BackgroundColor(Color::rgba(0.25, 0.25, 0.25, 0.75)),
+			visibility: Visibility::Hidden,
+			..default()
+		},
+	));
+}
+
+fn update_gui(editor: Res<Editor>, mut text: Query<&mut Text, With<EditorText>>) {
+	if !editor.is_changed() {
+		return;
+	}
+
+	let text = &mut text.single_mut().sections[0].value;
+	text.clear();
+	write!(text, "EDITOR  tool: {:?}  brush: ", editor.tool).unwrap();
+	match editor.brush() {
+		Some(tile) => write!(text, "{:?} ({:?})", tile.ty, tile.tileset).unwrap(),
+		None => text.push_str("erase"),
+	}
+	if let Some(rect) = editor.selection {
+		let size = rect.size() + 1;
+		write!(text, "  selection: {}x{}", size.x, size.y).unwrap();
+	}
+}
@@ -1,12 +1,21 @@
+use std::collections::HashMap;
+
 use anyhow::anyhow;
+use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::{Collider, CollisionGroups, Group, RigidBody, Sensor, SolverGroups};
 use bevy_rapier2d::render::ColliderDebugColor;
 use rand::seq::{IteratorRandom, SliceRandom};
 use rand::thread_rng;
 
-use crate::map::{FloorType, MutMap, Tile, TilePos, TileType, Tileset, WallShape};
-use crate::{AResult, InteractEvent, Interactible, IsoSprite};
+use crate::anim::{Animation, AnimationClips};
+use crate::audio::{PlaySound, SoundKind};
+use crate::interact::{InteractEvent, InteractKind, InteractResult, InteractSet};
+use crate::map::{
+	tileRadius, FloorType, MutMap, PluckedTile, Tile, TilePos, TileType, Tileset, WallShape,
+};
+use crate::save::{Restore, SaveDir, Serializer};
+use crate::{AResult, Interactible, IsoSprite};
 
 #[derive(Component)]
 pub struct Door(Tile, Collider);
@@ -26,15 +35,29 @@ impl Door {
 			_ => unreachable!(),
 		}
 	}
+
+	/// Asset path of the clip played when the door last toggled.
+	pub fn animation_path(&self) -> &'static str {
+		match self.0.ty {
+			TileType::DoorNS { open: true } => "animations/door_ns_open.ron",
+			TileType::DoorNS { open: false } => "animations/door_ns_close.ron",
+			TileType::DoorEW { open: true } => "animations/door_ew_open.ron",
+			TileType::DoorEW { open: false } => "animations/door_ew_close.ron",
+			_ => unreachable!(),
+		}
+	}
 }
 
 #[linkme::distributed_slice(crate::setupApp)]
 fn setup_app(app: &mut App) {
-	app.add_systems(Update, (update_doors, handle_interactions));
+	app.add_systems(
+		Update,
+		(update_doors, handle_interactions.in_set(InteractSet::Handle)),
+	);
 }
 
 #[linkme::distributed_slice(crate::setupMap)]
-fn setup_map(map: &mut MutMap, cmd: &mut Commands, assets: &AssetServer) {
+pub(crate) fn setup_map(map: &mut MutMap, cmd: &mut Commands, assets: &AssetServer) {
 	let doors = map.pluck_tiles(|_, pair| pair.is_door());
 	for (pos, tile) in doors {
 		let (sprite, _) = tile.into_bundle(pos.as_vec2(), assets);
@@ -50,8 +73,10 @@ fn setup_map(map: &mut MutMap, cmd: &mut Commands, assets: &AssetServer) {
 
 		cmd.spawn((
 			Door(tile, collider.clone()),
+			PluckedTile(pos),
 			sprite,
 			Interactible,
+			Name::new("Door"),
 			RigidBody::Fixed,
 			collider,
 			CollisionGroups::default(),
@@ -60,6 +85,41 @@ fn setup_map(map: &mut MutMap, cmd: &mut Commands, assets: &AssetServer) {
 	}
 }
 
+#[linkme::distributed_slice(crate::save::serializers)]
+static serializer: Serializer = Serializer {
+	name: "doors",
+	save: save_doors,
+	load: load_doors,
+};
+
+fn door_tile(transform: &Transform) -> TilePos {
+	let pos = (transform.translation.xy() / tileRadius).round();
+	TilePos::of(pos.x as _, pos.y as _)
+}
+
+/// Saves which doors are open. Doors themselves are respawned from the map.
+fn save_doors(world: &mut World, dir: &SaveDir) -> AResult<()> {
+	let mut query = world.query::<(&Door, &Transform)>();
+	let doors: HashMap<TilePos, bool> = query
+		.iter(world)
+		.map(|(door, transform)| (door_tile(transform), door.is_open()))
+		.collect();
+	dir.write("doors", &doors)
+}
+
+fn load_doors(dir: &SaveDir) -> AResult<Restore> {
+	let doors: HashMap<TilePos, bool> = dir.read("doors")?;
+	Ok(Box::new(move |world: &mut World| {
+		let mut query = world.query::<(&mut Door, &Transform)>();
+		for (mut door, transform) in query.iter_mut(world) {
+			let open = doors.get(&door_tile(transform)).copied().unwrap_or_default();
+			if door.is_open() != open {
+				door.toggle();
+			}
+		}
+	}))
+}
+
 fn update_doors(
 	mut cmd: Commands,
 	mut query: Query<
@@ -95,10 +155,36 @@ fn update_doors(
 
 fn handle_interactions(
 	mut cmd: Commands,
-	mut doors: Query<(Entity, &mut Door), Added<InteractEvent>>,
+	mut events: EventReader<InteractEvent>,
+	mut results: EventWriter<InteractResult>,
+	mut sounds: EventWriter<PlaySound>,
+	mut doors: Query<(&mut Door, &Transform)>,
+	assets: Res<AssetServer>,
+	mut clips: ResMut<AnimationClips>,
 ) {
-	for (ent, mut door) in &mut doors {
-		cmd.entity(ent).remove::<InteractEvent>();
-		door.toggle();
+	for &ev in &mut events {
+		let Ok((mut door, transform)) = doors.get_mut(ev.target) else {
+			continue;
+		};
+		let pos = transform.translation.xy();
+		match ev.kind {
+			InteractKind::Use => {
+				door.toggle();
+				let clip = clips.get(&assets, door.animation_path());
+				cmd.entity(ev.target).insert(Animation::new(clip));
+				let sound = if door.is_open() {
+					SoundKind::DoorOpen
+				} else {
+					SoundKind::DoorClose
+				};
+				sounds.send(PlaySound::at(sound, pos));
+				results.send(InteractResult::done(ev));
+			},
+			InteractKind::Attack => {
+				sounds.send(PlaySound::at(SoundKind::Hit, pos));
+				results.send(InteractResult::failed(ev, "The door is too sturdy to break."));
+			},
+			InteractKind::Examine => {},
+		}
 	}
 }
@@ -0,0 +1,194 @@
+//! Hit points. Damage and healing are requested with [`DamageEvent`] and
+//! [`HealEvent`] and applied together in `PostUpdate`, scaled by the source's
+//! strength and the target's resistances; [`DeathEvent`] follows when health
+//! runs out.
+
+use std::collections::HashMap;
+
+use bevy::ecs::query::Has;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::player::Player;
+use super::stats::Stats;
+use super::status::{StatusEffects, StatusKind};
+use crate::audio::{PlaySound, SoundKind};
+use crate::camera::ScreenShake;
+use crate::feed::{FeedCategory, FeedEntry, Severity};
+
+/// Screen shake trauma added per fraction of max health the player loses.
+const shakePerHealth: f32 = 3.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum DamageType {
+	Physical,
+	Fire,
+	Poison,
+	Arcane,
+}
+
+#[derive(Clone, Debug, Component, Deserialize, Serialize)]
+pub struct Health {
+	current: f32,
+	max: f32,
+}
+
+impl Health {
+	pub fn new(max: f32) -> Self {
+		Self { current: max, max }
+	}
+
+	pub fn current(&self) -> f32 {
+		self.current
+	}
+
+	pub fn max(&self) -> f32 {
+		self.max
+	}
+
+	/// Current health as a fraction of max, from 0 to 1.
+	pub fn fraction(&self) -> f32 {
+		(self.current / self.max).clamp(0.0, 1.0)
+	}
+
+	pub fn is_dead(&self) -> bool {
+		self.current <= 0.0
+	}
+
+	/// Sets max health, scaling current health along.
+	pub fn set_max(&mut self, max: f32) {
+		self.current *= max / self.max;
+		self.max = max;
+	}
+
+	/// Lowers health by `amount`, down to 0. Returns the damage taken.
+	fn damage(&mut self, amount: f32) -> f32 {
+		let taken = amount.min(self.current).max(0.0);
+		self.current -= taken;
+		taken
+	}
+
+	/// Raises health by `amount`, up to max. Returns the health restored.
+	fn heal(&mut self, amount: f32) -> f32 {
+		let restored = amount.min(self.max - self.current).max(0.0);
+		self.current += restored;
+		restored
+	}
+}
+
+/// Fraction of incoming damage ignored, by type. 1 makes immune; negative
+/// values are weaknesses.
+#[derive(Clone, Debug, Default, Component)]
+pub struct Resistances(pub HashMap<DamageType, f32>);
+
+impl Resistances {
+	pub fn get(&self, ty: DamageType) -> f32 {
+		self.0.get(&ty).copied().unwrap_or(0.0)
+	}
+}
+
+/// Hurts `target`. `source` is whoever or whatever dealt the damage, if
+/// anyone.
+#[derive(Clone, Copy, Debug, Event)]
+pub struct DamageEvent {
+	pub target: Entity,
+	pub source: Option<Entity>,
+	pub amount: f32,
+	pub ty: DamageType,
+}
+
+#[derive(Clone, Copy, Debug, Event)]
+pub struct HealEvent {
+	pub target: Entity,
+	pub source: Option<Entity>,
+	pub amount: f32,
+}
+
+/// `entity` ran out of health, last hurt by `source`.
+#[derive(Clone, Copy, Debug, Event)]
+pub struct DeathEvent {
+	pub entity: Entity,
+	pub source: Option<Entity>,
+}
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.add_event::<DamageEvent>();
+	app.add_event::<HealEvent>();
+	app.add_event::<DeathEvent>();
+	app.add_systems(PostUpdate, (apply_health, announce_player_death).chain());
+}
+
+fn apply_health(
+	mut damages: EventReader<DamageEvent>,
+	mut heals: EventReader<HealEvent>,
+	mut deaths: EventWriter<DeathEvent>,
+	mut shakes: EventWriter<ScreenShake>,
+	mut sounds: EventWriter<PlaySound>,
+	mut targets: Query<(
+		&mut Health,
+		&Transform,
+		Option<&Resistances>,
+		Option<&StatusEffects>,
+		Has<Player>,
+	)>,
+	sources: Query<&Stats>,
+) {
+	for ev in &mut heals {
+		if let Ok((mut health, ..)) = targets.get_mut(ev.target) {
+			if !health.is_dead() {
+				health.heal(ev.amount);
+			}
+		}
+	}
+
+	for ev in &mut damages {
+		let Ok((mut health, transform, resistances, effects, isPlayer)) =
+			targets.get_mut(ev.target)
+		else {
+			continue;
+		};
+		let invulnerable = effects.is_some_and(|effects| effects.has(StatusKind::Invulnerable));
+		if health.is_dead() || invulnerable {
+			continue;
+		}
+
+		let sourceStats = ev.source.and_then(|source| sources.get(source).ok());
+		let multiplier = sourceStats.map_or(1.0, Stats::damage);
+		let resistance = resistances.map_or(0.0, |resistances| resistances.get(ev.ty));
+		let taken = health.damage(ev.amount * multiplier * (1.0 - resistance));
+		if taken > 0.0 {
+			sounds.send(PlaySound::at(SoundKind::Hit, transform.translation.xy()));
+			if isPlayer {
+				shakes.send(ScreenShake((taken / health.max() * shakePerHealth).min(1.0)));
+			}
+		}
+		if health.is_dead() {
+			deaths.send(DeathEvent {
+				entity: ev.target,
+				source: ev.source,
+			});
+		}
+	}
+}
+
+fn announce_player_death(
+	mut deaths: EventReader<DeathEvent>,
+	player: Query<(), With<Player>>,
+	names: Query<&Name>,
+) {
+	for ev in &mut deaths {
+		if !player.contains(ev.entity) {
+			continue;
+		}
+		let text = match ev.source.and_then(|source| names.get(source).ok()) {
+			Some(name) => format!("You were slain by a {name}."),
+			None => "You died.".into(),
+		};
+		FeedEntry::new(text)
+			.category(FeedCategory::Combat)
+			.severity(Severity::Bad)
+			.submit();
+	}
+}
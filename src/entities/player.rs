@@ -1,9 +1,7 @@
-use std::cmp::Ordering;
+use std::collections::VecDeque;
 use std::f32::consts::PI;
 use std::fmt::Write;
-use std::sync::{mpsc, Once, OnceLock};
 
-use bevy::input::mouse::MouseWheel;
 use bevy::math::{vec2, vec3, Vec3Swizzles};
 use bevy::prelude::*;
 use bevy::render::primitives::Aabb;
@@ -11,10 +9,21 @@ use bevy::window::PrimaryWindow;
 use bevy_rapier2d::prelude::*;
 use rand::seq::SliceRandom;
 use rand::{thread_rng, Rng};
-
-use super::Health;
-use crate::map::{tileDiameter, tileRadius, FloorType, Landmark, MutMap, Tile, TileType};
-use crate::{find_interactible_entities, world_to_iso, InteractEvent, IsoSprite, IsoSpriteBundle};
+use serde::{Deserialize, Serialize};
+
+use super::stats::{Experience, Stats};
+use super::status::StatusEffects;
+use super::health::Health;
+use crate::anim::{Animation, AnimationClips};
+use crate::camera::CameraRig;
+use crate::feed::{FeedEntry, Severity};
+use crate::input::{Action, MoveAxis};
+use crate::light::Light;
+use crate::map::{
+	tileDiameter, tileRadius, FloorType, Landmark, MutMap, Occluder, Tile, TilePos, TileType,
+};
+use crate::save::{Restore, SaveDir, Serializer};
+use crate::{world_to_iso, AResult, IsoSprite, IsoSpriteBundle, RenderLayer};
 
 pub const depthRange: f32 = 1_000_000.0;
 
@@ -24,6 +33,39 @@ pub struct Player;
 #[derive(Component)]
 pub struct Cursor;
 
+/// Drained by sprinting and regained while not, faster when standing still.
+/// Running out exhausts the player, who can't sprint and walks slower until
+/// they've recovered some.
+#[derive(Clone, Debug, Component, Deserialize, Serialize)]
+pub struct Stamina {
+	pub current: f32,
+	pub max: f32,
+	pub exhausted: bool,
+}
+
+impl Stamina {
+	pub fn new(max: f32) -> Self {
+		Self {
+			current: max,
+			max,
+			exhausted: false,
+		}
+	}
+
+	pub fn fraction(&self) -> f32 {
+		(self.current / self.max).clamp(0.0, 1.0)
+	}
+}
+
+/// Tiles the player walks along after clicking somewhere, until they arrive
+/// or take over with the movement controls.
+#[derive(Clone, Debug, Default, Component)]
+pub struct WalkPath {
+	pub tiles: VecDeque<TilePos>,
+	/// Interactible to use once it's in reach.
+	pub target: Option<Entity>,
+}
+
 #[linkme::distributed_slice(crate::setupApp)]
 fn setup_app(app: &mut App) {
 	app.add_systems(Startup, (startup, startup_gui));
@@ -31,10 +73,8 @@ fn setup_app(app: &mut App) {
 		Update,
 		(
 			move_player,
-			move_camera.after(move_player),
-			zoom_camera,
+			fade_occluders.after(move_player),
 			move_cursor,
-			interact.after(move_cursor),
 			#[cfg(debug_assertions)]
 			teleport,
 			update_gui,
@@ -53,15 +93,85 @@ fn setup_map(map: &mut MutMap, cmd: &mut Commands, assets: &AssetServer) {
 			}
 		)
 	});
-	// FIXME: use map rng
-	let playerSpawn = playerSpawns.choose(&mut thread_rng()).unwrap().0;
+	let playerSpawn = playerSpawns.choose(&mut *map.rng()).unwrap().0;
 	cmd.add(move |world: &mut World| {
 		let mut query = world.query_filtered::<&mut Transform, With<Player>>();
 		query.single_mut(world).translation = (playerSpawn.as_vec2() * tileRadius, 0.0).into();
 	});
 }
 
-pub fn startup(mut cmd: Commands, assets: Res<AssetServer>) {
+/// The parts of the player a new game starts over.
+fn fresh_state() -> impl Bundle {
+	let stats = Stats::default();
+	(
+		Health::new(stats.max_health()),
+		stats,
+		Experience::default(),
+		Stamina::new(100.0),
+		StatusEffects::default(),
+	)
+}
+
+/// Resets the player for a new game.
+pub fn reset(cmd: &mut Commands, player: Entity) {
+	cmd.entity(player).remove::<WalkPath>().insert(fresh_state());
+}
+
+#[derive(Deserialize, Serialize)]
+struct PlayerSave {
+	pos: Vec2,
+	health: Health,
+	stats: Stats,
+	xp: Experience,
+	stamina: Stamina,
+	effects: StatusEffects,
+}
+
+#[linkme::distributed_slice(crate::save::serializers)]
+static serializer: Serializer = Serializer {
+	name: "player",
+	save: save_player,
+	load: load_player,
+};
+
+fn save_player(world: &mut World, dir: &SaveDir) -> AResult<()> {
+	let mut query = world.query_filtered::<
+		(&Transform, &Health, &Stats, &Experience, &Stamina, &StatusEffects),
+		With<Player>,
+	>();
+	let (transform, health, stats, xp, stamina, effects) = query.single(world);
+	dir.write(
+		"player",
+		&PlayerSave {
+			pos: transform.translation.xy(),
+			health: health.clone(),
+			stats: stats.clone(),
+			xp: xp.clone(),
+			stamina: stamina.clone(),
+			effects: effects.clone(),
+		},
+	)
+}
+
+fn load_player(dir: &SaveDir) -> AResult<Restore> {
+	let save: PlayerSave = dir.read("player")?;
+	Ok(Box::new(move |world: &mut World| {
+		let mut query = world.query_filtered::<Entity, With<Player>>();
+		let player = query.single(world);
+		let mut player = world.entity_mut(player);
+		player.get_mut::<Transform>().unwrap().translation = (save.pos, 0.0).into();
+		player.remove::<WalkPath>().insert((
+			save.health,
+			save.stats,
+			save.xp,
+			save.stamina,
+			save.effects,
+			Velocity::default(),
+		));
+	}))
+}
+
+pub fn startup(mut cmd: Commands, assets: Res<AssetServer>, mut clips: ResMut<AnimationClips>) {
 	let (texture, playerRect, _) = Tile {
 		ty: TileType::Landmark {
 			ty: Landmark::SpawnPlayer,
@@ -73,15 +183,19 @@ pub fn startup(mut cmd: Commands, assets: Res<AssetServer>) {
 	let texture = assets.load(texture);
 	cmd.spawn((
 		Player,
-		Health::new(100.0),
+		fresh_state(),
 		IsoSpriteBundle {
 			texture: texture.clone(),
 			sprite: IsoSprite {
 				rect: playerRect,
-				flip: false,
+				..default()
 			},
 			..default()
 		},
+		Animation {
+			paused: true,
+			..Animation::new(clips.get(&assets, "animations/player_walk.ron"))
+		},
 		RigidBody::Dynamic,
 		LockedAxes::ROTATION_LOCKED,
 		Dominance::group(64),
@@ -92,6 +206,7 @@ pub fn startup(mut cmd: Commands, assets: Res<AssetServer>) {
 		},
 		Collider::ball(tileRadius / 5.0),
 		ColliderDebugColor(Color::YELLOW),
+		Light::torch,
 	));
 
 	let (_, cursorRect, _) = Tile {
@@ -108,50 +223,122 @@ pub fn startup(mut cmd: Commands, assets: Res<AssetServer>) {
 			texture,
 			sprite: IsoSprite {
 				rect: cursorRect,
-				flip: false,
+				layer: RenderLayer::WorldUi,
+				..default()
 			},
 			..default()
 		},
 	));
-
-	cmd.spawn(Camera2dBundle {
-		projection: OrthographicProjection {
-			far: depthRange,
-			..default()
-		},
-		..default()
-	});
 }
 
 fn move_player(
-	mut playerQuery: Query<(&mut Velocity, &mut IsoSprite), With<Player>>,
+	mut cmd: Commands,
+	mut playerQuery: Query<
+		(
+			Entity,
+			&Transform,
+			&mut Velocity,
+			&mut IsoSprite,
+			&mut Animation,
+			&Stats,
+			&StatusEffects,
+			&mut Stamina,
+			Option<&mut WalkPath>,
+		),
+		With<Player>,
+	>,
 	time: Res<Time>,
-	keyboard: Res<Input<KeyCode>>,
+	actions: Res<Input<Action>>,
+	moveAxis: Res<MoveAxis>,
+	camera: Query<&CameraRig>,
 	mut lastRngFlip: Local<f64>,
 ) {
-	let mut vel = Vec2::ZERO;
-	if keyboard.pressed(KeyCode::W) {
-		vel.y -= 1.0;
-	}
-	if keyboard.pressed(KeyCode::S) {
-		vel.y += 1.0;
-	}
-	if keyboard.pressed(KeyCode::A) {
-		vel.x -= 1.0;
+	// how close the player has to get to a path tile's center to move on to the next one
+	const waypointRadius: f32 = 4.0;
+	// stamina per second
+	const sprintDrain: f32 = 25.0;
+	const walkingRegen: f32 = 10.0;
+	const restingRegen: f32 = 25.0;
+	// fraction of max stamina needed to shake off exhaustion
+	const recoverAt: f32 = 0.3;
+	const exhaustedSpeed: f32 = 0.6;
+
+	let (
+		ent,
+		transform,
+		mut velocity,
+		mut sprite,
+		mut anim,
+		stats,
+		effects,
+		mut stamina,
+		path,
+	) = playerQuery.single_mut();
+	let pos = transform.translation.xy();
+
+	// the free camera takes over the movement controls
+	let mut vel = if camera.single().free {
+		Vec2::ZERO
+	} else {
+		// world space y points down the screen
+		moveAxis.0 * vec2(1.0, -1.0)
+	};
+
+	if let Some(mut path) = path {
+		if vel != Vec2::ZERO {
+			cmd.entity(ent).remove::<WalkPath>();
+		} else {
+			while let Some(&next) = path.tiles.front() {
+				let offset = next.as_vec2() * tileRadius - pos;
+				if offset.length() > waypointRadius {
+					vel = offset.normalize();
+					break;
+				}
+				path.tiles.pop_front();
+			}
+			// paths with a target are cleaned up once it's used
+			if path.tiles.is_empty() && path.target.is_none() {
+				cmd.entity(ent).remove::<WalkPath>();
+			}
+		}
 	}
-	if keyboard.pressed(KeyCode::D) {
-		vel.x += 1.0;
+
+	let dt = time.delta_seconds();
+	let walking = vel.length_squared() > 0.0;
+	let sprinting = walking && !stamina.exhausted && actions.pressed(Action::Sprint);
+	if sprinting {
+		stamina.current = (stamina.current - sprintDrain * dt).max(0.0);
+		if stamina.current <= 0.0 {
+			stamina.exhausted = true;
+			FeedEntry::new("You are exhausted!")
+				.severity(Severity::Warning)
+				.submit();
+		}
+	} else if stamina.current < stamina.max {
+		let regen = if walking { walkingRegen } else { restingRegen };
+		stamina.current = (stamina.current + regen * dt).min(stamina.max);
+		if stamina.exhausted && stamina.fraction() >= recoverAt {
+			stamina.exhausted = false;
+		}
 	}
-	vel = vel.normalize_or_zero();
 
-	let sprint = if keyboard.pressed(KeyCode::ShiftLeft) {
-		4.0
+	let sprint = if sprinting {
+		stats.sprint()
+	} else if stamina.exhausted {
+		exhaustedSpeed
 	} else {
 		1.0
 	};
-
-	let (mut velocity, mut sprite) = playerQuery.single_mut();
-	velocity.linvel = vel.normalize_or_zero() * tileDiameter * sprint;
+	let speed = stats.speed() * sprint * effects.speed_multiplier();
+	velocity.linvel = vel * tileDiameter * speed;
+
+	// stand still on the first frame of the walk cycle
+	if anim.paused == walking {
+		anim.paused = !walking;
+		if !walking {
+			anim.restart();
+		}
+	}
 
 	// flip sprite to match movement direction
 	if vel.length_squared() > 0.0 {
@@ -175,99 +362,77 @@ fn move_player(
 	}
 }
 
-fn move_camera(
-	playerQuery: Query<&Transform, With<Player>>,
-	mut cameraQuery: Query<&mut Transform, (With<Camera2d>, Without<Player>)>,
-) {
-	let mut pos = world_to_iso(playerQuery.single().translation.xy());
-	pos.z = depthRange;
-	cameraQuery.single_mut().translation = pos;
-}
-
-fn zoom_camera(
-	mut query: Query<&mut OrthographicProjection, With<Camera2d>>,
-	keyboard: Res<Input<KeyCode>>,
-	mut mouseWheel: EventReader<MouseWheel>,
+/// Fades out walls and tall landmarks that stand in front of the player and
+/// cover them on screen.
+fn fade_occluders(
+	playerQuery: Query<(&GlobalTransform, &IsoSprite), With<Player>>,
+	mut occluders: Query<(&GlobalTransform, &mut IsoSprite), (With<Occluder>, Without<Player>)>,
+	time: Res<Time>,
 ) {
-	const step: f32 = 0.1;
-	const min: f32 = 0.1;
-	const max: f32 = 2.5;
-
-	let mut scrollDelta = 0.0;
-	for ev in &mut mouseWheel {
-		scrollDelta += ev.y;
+	const fadedAlpha: f32 = 0.35;
+	// alpha per second
+	const fadeSpeed: f32 = 4.0;
+
+	let (transform, player) = playerQuery.single();
+	let playerPos = transform.translation().xy();
+	let playerDepth = player.layer.depth(player.sort_point(playerPos));
+	// the character only fills the middle of its sprite
+	let playerRect = player.screen_rect(playerPos);
+	let playerRect = Rect::from_center_half_size(playerRect.center(), playerRect.half_size() / 2.0);
+
+	let step = fadeSpeed * time.delta_seconds();
+	for (transform, mut sprite) in &mut occluders {
+		let pos = transform.translation().xy();
+		let inFront = sprite.layer.depth(sprite.sort_point(pos)) > playerDepth;
+		let covers = inFront && !sprite.screen_rect(pos).intersect(playerRect).is_empty();
+		let target = if covers { fadedAlpha } else { 1.0 };
+
+		let alpha = sprite.color.a();
+		if alpha != target {
+			sprite.color.set_a(alpha + (target - alpha).clamp(-step, step));
+		}
 	}
-
-	let add = if keyboard.just_pressed(KeyCode::Equals) || scrollDelta > 0.0 {
-		-step
-	} else if keyboard.just_pressed(KeyCode::Minus) || scrollDelta < 0.0 {
-		step
-	} else {
-		return;
-	};
-	let mut projection = query.single_mut();
-	projection.scale = (projection.scale.ln() + add).exp().clamp(min, max);
 }
 
-fn move_cursor(
+pub fn move_cursor(
 	mut cursor: Query<&mut Transform, With<Cursor>>,
 	camera: Query<(&Camera, &GlobalTransform)>,
 	window: Query<&Window, With<PrimaryWindow>>,
 	mut lastPos: Local<Vec2>,
 ) {
-	let mut pos = window.single().cursor_position().unwrap_or(*lastPos);
+	// headless, the cursor stays wherever it was put
+	let Ok(window) = window.get_single() else {
+		return;
+	};
+	let mut pos = window.cursor_position().unwrap_or(*lastPos);
 	*lastPos = pos;
 
 	let (camera, transform) = camera.single();
-	pos = camera.viewport_to_world_2d(transform, pos).unwrap();
-	pos = crate::iso_to_world(pos);
+	let Some(worldPos) = camera.viewport_to_world_2d(transform, pos) else {
+		return;
+	};
+	pos = crate::iso_to_world(worldPos);
 
 	// snap to tile
 	pos /= tileRadius;
 	pos = vec2(pos.x.ceil(), pos.y.floor());
 	pos *= tileRadius;
 
-	cursor.single_mut().translation = (pos, tileDiameter).into();
-}
-
-fn interact(world: &mut World) {
-	const reachDistance: f32 = tileDiameter * 0.75;
-
-	let keyboard: &Input<KeyCode> = world.resource();
-	if keyboard.just_pressed(KeyCode::E) {
-		let mut cursor = world.query_filtered::<&Transform, With<Cursor>>();
-		let mut player = world.query_filtered::<(Entity, &Transform), With<Player>>();
-
-		let cursorPos = cursor.single(&world).translation.xy();
-		let (player, plyPos) = player.single(&world);
-		let plyPos = plyPos.translation.xy();
-
-		if cursorPos.distance_squared(plyPos) > reachDistance.powf(2.0) {
-			return;
-		}
-
-		let ents = find_interactible_entities(cursorPos, 8.0, world);
-		let Some(&target) = ents.first() else {
-			return;
-		};
-		world
-			.entity_mut(target)
-			.insert(InteractEvent { source: player });
-	}
+	cursor.single_mut().translation = (pos, 0.0).into();
 }
 
 #[cfg(debug_assertions)]
 fn teleport(
 	mut player: Query<&mut Transform, With<Player>>,
-	keyboard: Res<Input<KeyCode>>,
+	actions: Res<Input<Action>>,
 	mut savedPos: Local<Option<Vec2>>,
 ) {
-	if keyboard.just_pressed(KeyCode::Y) {
+	if actions.just_pressed(Action::SavePosition) {
 		*savedPos = Some(player.single().translation.xy());
 		eprintln!("saved position {:?}", savedPos.unwrap());
 	}
 
-	if keyboard.just_pressed(KeyCode::T) {
+	if actions.just_pressed(Action::RecallPosition) {
 		if let Some(pos) = *savedPos {
 			let translation = &mut player.single_mut().translation;
 			*translation = (pos, translation.z).into();
@@ -283,29 +448,7 @@ struct HealthBarRect;
 struct HealthBarText;
 
 #[derive(Component)]
-struct Feed;
-
-#[derive(Clone, Copy, Component)]
-struct FeedMessage(f32);
-
-type FeedQueue = (mpsc::Sender<String>, mpsc::Receiver<String>);
-static mut feedQueue: OnceLock<FeedQueue> = OnceLock::new();
-
-fn get_feed_queue() -> &'static FeedQueue {
-	unsafe { &feedQueue }.get_or_init(|| mpsc::channel())
-}
-
-pub fn submit_feed_message(msg: String) {
-	get_feed_queue().0.send(msg);
-}
-
-#[macro_export]
-macro_rules! print_feed {
-	($($args:tt)*) => {
-		crate::entities::player::submit_feed_message(format!($($args)*))
-	};
-}
-use print_feed;
+struct StaminaBarRect;
 
 fn startup_gui(mut cmd: Commands, assets: Res<AssetServer>) {
 	let (width, height) = (200.0, 50.0);
@@ -364,84 +507,63 @@ fn startup_gui(mut cmd: Commands, assets: Res<AssetServer>) {
 		));
 	});
 
-	cmd.spawn((
-		Feed,
-		NodeBundle {
-			style: Style {
-				max_width: Val::Vw(100.0),
-				padding: UiRect::all(Val::Px(5.0)),
-				flex_direction: FlexDirection::Column,
-				position_type: PositionType::Absolute,
-				top: Val::Percent(0.0),
-				left: Val::Percent(0.0),
-				..default()
-			},
-			background_color: BackgroundColor(Color::rgba(0.25, 0.25, 0.25, 0.75)),
+	// next to the health bar
+	cmd.spawn(NodeBundle {
+		style: Style {
+			width: Val::Px(width * 0.75),
+			height: Val::Px(height / 2.0),
+			position_type: PositionType::Absolute,
+			bottom: Val::Px(10.0),
+			left: Val::Px(width + 20.0),
 			..default()
 		},
-	));
+		background_color: BackgroundColor(Color::GRAY),
+		..default()
+	})
+	.with_children(|parent| {
+		parent.spawn((
+			StaminaBarRect,
+			NodeBundle {
+				style: Style {
+					width: Val::Percent(100.0),
+					height: Val::Percent(80.0),
+					position_type: PositionType::Absolute,
+					top: Val::Percent(10.0),
+					left: Val::Percent(0.0),
+					..default()
+				},
+				background_color: BackgroundColor(Color::YELLOW_GREEN),
+				..default()
+			},
+		));
+	});
 }
 
 fn update_gui(
-	mut cmd: Commands,
-
 	health: Query<&Health, (With<Player>, Changed<Health>)>,
 	mut healthRect: Query<&mut Style, With<HealthBarRect>>,
 	mut healthText: Query<&mut Text, With<HealthBarText>>,
-
-	feedBox: Query<Entity, With<Feed>>,
-	messages: Query<(Entity, &FeedMessage)>,
-
-	assets: Res<AssetServer>,
-	time: Res<Time>,
-
-	mut feedMessageEnts: Local<Vec<(Entity, FeedMessage)>>,
+	stamina: Query<&Stamina, (With<Player>, Changed<Stamina>)>,
+	mut staminaRect: Query<
+		(&mut Style, &mut BackgroundColor),
+		(With<StaminaBarRect>, Without<HealthBarRect>),
+	>,
 ) {
-	const feedFontSize: f32 = 24.0;
-	const feedMaxMessages: usize = 10;
-
-	for &Health(health) in &health {
-		healthRect.single_mut().width = Val::Percent(health);
+	for health in &health {
+		healthRect.single_mut().width = Val::Percent(health.fraction() * 100.0);
 
 		let text = &mut healthText.single_mut().sections[0].value;
 		text.clear();
-		write!(text, "{health:.0}").unwrap();
+		write!(text, "{:.0}", health.current()).unwrap();
 	}
 
-	let mut newMessages = false;
-	let feedBox = feedBox.single();
-	while let Ok(msg) = get_feed_queue().1.try_recv() {
-		newMessages = true;
-		let child = cmd
-			.spawn((
-				FeedMessage(time.elapsed_seconds()),
-				TextBundle {
-					text: Text::from_section(
-						msg,
-						TextStyle {
-							font: assets.load("fonts/RedHatDisplay.ttf"),
-							font_size: feedFontSize,
-							color: Color::WHITE,
-						},
-					),
-					..default()
-				},
-			))
-			.id();
-		cmd.entity(feedBox).add_child(child);
-	}
-	if newMessages {
-		feedMessageEnts.clear();
-		feedMessageEnts.extend(messages.into_iter().map(|(id, &msg)| (id, msg)));
-		if feedMessageEnts.len() > feedMaxMessages {
-			feedMessageEnts.sort_by(|&(_, l), &(_, r)| {
-				l.0.partial_cmp(&r.0).unwrap_or(Ordering::Equal).reverse()
-			});
-			while feedMessageEnts.len() > feedMaxMessages {
-				let ent = feedMessageEnts.pop().unwrap().0;
-				cmd.entity(feedBox).remove_children(&[ent]);
-				cmd.entity(ent).despawn();
-			}
-		}
+	for stamina in &stamina {
+		let (mut style, mut color) = staminaRect.single_mut();
+		style.width = Val::Percent(stamina.fraction() * 100.0);
+		color.0 = if stamina.exhausted {
+			Color::ORANGE_RED
+		} else {
+			Color::YELLOW_GREEN
+		};
 	}
 }
@@ -1,14 +1,26 @@
+use std::collections::HashMap;
+
 use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::{Collider, RigidBody};
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
-use super::player::Player;
-use super::Health;
-use crate::map::{tileDiameter, tileRadius, FloorType, Landmark, Map, MutMap, TilePos, TileType};
-use crate::{print_feed, InteractEvent, Interactible, IsoSpriteBundle};
+use super::status::{StatusEffect, StatusEffects, StatusKind};
+use super::health::{DamageEvent, DamageType, HealEvent};
+use crate::anim::{spawn_effect, AnimationClips};
+use crate::audio::{PlaySound, SoundKind};
+use crate::camera::ScreenShake;
+use crate::feed::{FeedCategory, FeedEntry, Severity};
+use crate::interact::{InteractEvent, InteractKind, InteractResult, InteractSet};
+use crate::map::{
+	tileDiameter, tileRadius, FloorType, Landmark, Map, MutMap, PluckedTile, TilePos, TileType,
+};
+use crate::particles::{spawn_particles, ParticlePresets};
+use crate::rng::GameRng;
+use crate::save::{Restore, SaveDir, Serializer};
+use crate::{AResult, Interactible, IsoSpriteBundle};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum ShrineType {
 	Heal,
@@ -27,16 +39,35 @@ impl From<Landmark> for ShrineType {
 	}
 }
 
+impl ShrineType {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Heal => "Shrine of Healing",
+			Self::Damage => "Shrine of Pain",
+			Self::Blink => "Shrine of Blinking",
+		}
+	}
+}
+
 #[derive(Component)]
-pub struct Shrine(ShrineType);
+pub struct Shrine {
+	pub ty: ShrineType,
+	/// Times the shrine has been used.
+	pub uses: u32,
+}
 
 #[linkme::distributed_slice(crate::setupApp)]
 fn setup_app(app: &mut App) {
-	app.add_systems(Update, handle_interactions);
+	app.add_systems(
+		Update,
+		handle_interactions
+			.in_set(InteractSet::Handle)
+			.run_if(resource_exists::<Map>()),
+	);
 }
 
 #[linkme::distributed_slice(crate::setupMap)]
-fn setup_map(map: &mut MutMap, cmd: &mut Commands, assets: &AssetServer) {
+pub(crate) fn setup_map(map: &mut MutMap, cmd: &mut Commands, assets: &AssetServer) {
 	const radius: f32 = tileRadius / 2.0 * 0.9;
 	let collider = Collider::cuboid(radius, radius);
 
@@ -55,50 +86,163 @@ fn setup_map(map: &mut MutMap, cmd: &mut Commands, assets: &AssetServer) {
 			unreachable!()
 		};
 		let (sprite, _) = tile.into_bundle(pos.as_vec2(), assets);
+		let ty = ShrineType::from(landmark);
 		cmd.spawn((
-			Shrine(ShrineType::from(landmark)),
+			Shrine { ty, uses: 0 },
+			PluckedTile(pos),
 			sprite,
 			Interactible,
+			Name::new(ty.name()),
 			RigidBody::Fixed,
 			collider.clone(),
 		));
 	}
 }
 
+#[linkme::distributed_slice(crate::save::serializers)]
+static serializer: Serializer = Serializer {
+	name: "shrines",
+	save: save_shrines,
+	load: load_shrines,
+};
+
+fn shrine_tile(transform: &Transform) -> TilePos {
+	let pos = (transform.translation.xy() / tileRadius).round();
+	TilePos::of(pos.x as _, pos.y as _)
+}
+
+/// Saves how often each shrine has been used. Shrines themselves are
+/// respawned from the map.
+fn save_shrines(world: &mut World, dir: &SaveDir) -> AResult<()> {
+	let mut query = world.query::<(&Shrine, &Transform)>();
+	let uses: HashMap<TilePos, u32> = query
+		.iter(world)
+		.map(|(shrine, transform)| (shrine_tile(transform), shrine.uses))
+		.collect();
+	dir.write("shrines", &uses)
+}
+
+fn load_shrines(dir: &SaveDir) -> AResult<Restore> {
+	let uses: HashMap<TilePos, u32> = dir.read("shrines")?;
+	Ok(Box::new(move |world: &mut World| {
+		let mut query = world.query::<(&mut Shrine, &Transform)>();
+		for (mut shrine, transform) in query.iter_mut(world) {
+			shrine.uses = uses.get(&shrine_tile(transform)).copied().unwrap_or_default();
+		}
+	}))
+}
+
 fn handle_interactions(
 	mut cmd: Commands,
-	mut shrines: Query<(Entity, &InteractEvent, &Shrine), Added<InteractEvent>>,
-	mut player: Query<(&mut Transform, &mut Health), With<Player>>,
+	mut events: EventReader<InteractEvent>,
+	mut results: EventWriter<InteractResult>,
+	mut shrines: Query<&mut Shrine>,
+	mut actors: Query<(&mut Transform, Option<&mut StatusEffects>)>,
+	mut damages: EventWriter<DamageEvent>,
+	mut heals: EventWriter<HealEvent>,
 	map: Res<Map>,
+	assets: Res<AssetServer>,
+	mut clips: ResMut<AnimationClips>,
+	mut particles: ResMut<ParticlePresets>,
+	mut shakes: EventWriter<ScreenShake>,
+	mut sounds: EventWriter<PlaySound>,
+	mut rng: ResMut<GameRng>,
 ) {
-	for (shrineEnt, ev, shrine) in &mut shrines {
-		cmd.entity(shrineEnt).remove::<InteractEvent>();
-		match shrine.0 {
+	for &ev in &mut events {
+		let Ok(mut shrine) = shrines.get_mut(ev.target) else {
+			continue;
+		};
+		match ev.kind {
+			InteractKind::Use => {},
+			InteractKind::Attack => {
+				results.send(InteractResult::failed(ev, "The shrine shrugs off your blow."));
+				continue;
+			},
+			InteractKind::Examine => continue,
+		}
+		let Ok((mut transform, mut effects)) = actors.get_mut(ev.source) else {
+			continue;
+		};
+		// answered up front, as blinking can bail out below
+		results.send(InteractResult::done(ev));
+		shrine.uses += 1;
+		match shrine.ty {
 			ShrineType::Heal => {
-				print_feed!("The shrine heals you for 25 HP!");
-				player.single_mut().1.take_healing(25.0);
+				FeedEntry::new("The shrine heals you for 25 HP!")
+					.category(FeedCategory::Interaction)
+					.severity(Severity::Good)
+					.icon(Landmark::ShrineIdol)
+					.submit();
+				heals.send(HealEvent {
+					target: ev.source,
+					source: Some(ev.target),
+					amount: 25.0,
+				});
+				if let Some(effects) = &mut effects {
+					let regen = StatusEffect::new(StatusKind::Regeneration, 5.0);
+					effects.apply(regen.with_source(ev.target));
+				}
+				let pos = transform.translation.xy();
+				let preset = particles.get(&assets, "particles/healing.ron");
+				spawn_particles(&mut cmd, &assets, preset, pos);
+				sounds.send(PlaySound::at(SoundKind::ShrineHeal, pos));
 			},
 			ShrineType::Damage => {
-				print_feed!("The shrine damages you for 10 HP!");
-				player.single_mut().1.take_damage(10.0);
+				FeedEntry::new("The shrine damages you for 10 HP!")
+					.category(FeedCategory::Interaction)
+					.severity(Severity::Bad)
+					.icon(Landmark::ShrineSkulls)
+					.submit();
+				damages.send(DamageEvent {
+					target: ev.source,
+					source: Some(ev.target),
+					amount: 10.0,
+					ty: DamageType::Arcane,
+				});
+				if let Some(effects) = &mut effects {
+					let poison = StatusEffect::new(StatusKind::Poison, 6.0);
+					effects.apply(poison.with_source(ev.target));
+				}
+				let pos = transform.translation.xy();
+				let clip = clips.get(&assets, "animations/explosion_red.ron");
+				spawn_effect(&mut cmd, &assets, clip, pos);
+				shakes.send(ScreenShake(0.6));
+				sounds.send(PlaySound::at(SoundKind::ShrineDamage, pos));
+				for path in ["particles/explosion.ron", "particles/damage.ron"] {
+					spawn_particles(&mut cmd, &assets, particles.get(&assets, path), pos);
+				}
 			},
 			ShrineType::Blink => {
-				print_feed!("The shrine drives you through the aether!");
+				FeedEntry::new("The shrine drives you through the aether!")
+					.category(FeedCategory::Interaction)
+					.icon(Landmark::ShrineScroll)
+					.submit();
 
-				let transform = &mut player.single_mut().0;
 				let usedTiles = map.used_tiles();
 				let pos = {
-					let x = thread_rng().gen_range(usedTiles.min.x ..= usedTiles.max.x);
-					let y = thread_rng().gen_range(usedTiles.min.y ..= usedTiles.max.y);
+					let x = rng.gen_range(usedTiles.min.x ..= usedTiles.max.x);
+					let y = rng.gen_range(usedTiles.min.y ..= usedTiles.max.y);
 					TilePos::of(x, y)
 				};
-				let Some(newPos) = map.find_tile(pos, |_, tile| tile.is_floor()) else {
+				let here = (transform.translation.xy() / tileRadius).round();
+				let here = TilePos::of(here.x as _, here.y as _);
+				let newPos = map.find_tile(pos, |pos, tile| pos != here && tile.is_floor());
+				let Some(newPos) = newPos else {
 					continue;
 				};
+				let smoke = clips.get(&assets, "animations/smoke_light.ron");
+				let swirl = particles.get(&assets, "particles/teleport.ron");
+				for pos in [transform.translation.xy(), newPos.as_vec2() * tileRadius] {
+					spawn_effect(&mut cmd, &assets, smoke.clone(), pos);
+					spawn_particles(&mut cmd, &assets, swirl.clone(), pos);
+				}
 				transform.translation =
 					(newPos.as_vec2() * tileRadius, transform.translation.z).into();
+				sounds.send(PlaySound::at(SoundKind::Teleport, newPos.as_vec2() * tileRadius));
+				if let Some(effects) = &mut effects {
+					effects.apply(StatusEffect::new(StatusKind::Haste, 5.0));
+				}
 			},
-			_ => todo!("new shrine type {:?}", shrine.0),
 		}
 	}
 }
@@ -0,0 +1,359 @@
+//! Character attributes and experience. [`Stats`] feed max health, movement
+//! speed, reach and damage dealt; [`Experience`] comes from kills and from
+//! exploring new parts of the map, and each level grants points to spend on
+//! the character sheet (C by default).
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use bevy::ecs::query::Has;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::health::{DeathEvent, Health};
+use super::player::Player;
+use crate::feed::{FeedCategory, FeedEntry, Severity};
+use crate::input::{Action, BindingsScreen};
+use crate::map::{tileDiameter, tileRadius, ChunkPos, MutMap, TilePos};
+use crate::save::{Restore, SaveDir, Serializer};
+use crate::AResult;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stat {
+	Strength,
+	Agility,
+	Vitality,
+	Perception,
+}
+
+impl Stat {
+	pub const all: &'static [Self] = &[
+		Self::Strength,
+		Self::Agility,
+		Self::Vitality,
+		Self::Perception,
+	];
+
+	fn description(self) -> &'static str {
+		match self {
+			Self::Strength => "damage dealt",
+			Self::Agility => "movement and sprint speed",
+			Self::Vitality => "max health",
+			Self::Perception => "reach",
+		}
+	}
+}
+
+/// Attributes; 10 is average.
+#[derive(Clone, Debug, Component, Deserialize, Serialize)]
+pub struct Stats {
+	pub strength: u32,
+	pub agility: u32,
+	pub vitality: u32,
+	pub perception: u32,
+}
+
+impl Default for Stats {
+	fn default() -> Self {
+		Self {
+			strength: 10,
+			agility: 10,
+			vitality: 10,
+			perception: 10,
+		}
+	}
+}
+
+impl Stats {
+	pub fn get(&self, stat: Stat) -> u32 {
+		match stat {
+			Stat::Strength => self.strength,
+			Stat::Agility => self.agility,
+			Stat::Vitality => self.vitality,
+			Stat::Perception => self.perception,
+		}
+	}
+
+	fn get_mut(&mut self, stat: Stat) -> &mut u32 {
+		match stat {
+			Stat::Strength => &mut self.strength,
+			Stat::Agility => &mut self.agility,
+			Stat::Vitality => &mut self.vitality,
+			Stat::Perception => &mut self.perception,
+		}
+	}
+
+	/// Difference from average, for scaling derived values.
+	fn bonus(&self, stat: Stat) -> f32 {
+		self.get(stat) as f32 - 10.0
+	}
+
+	pub fn max_health(&self) -> f32 {
+		(100.0 + 5.0 * self.bonus(Stat::Vitality)).max(1.0)
+	}
+
+	/// Multiplier for walking speed.
+	pub fn speed(&self) -> f32 {
+		(1.0 + 0.03 * self.bonus(Stat::Agility)).max(0.1)
+	}
+
+	/// Multiplier for speed while sprinting, on top of [`Self::speed`].
+	pub fn sprint(&self) -> f32 {
+		(4.0 + 0.1 * self.bonus(Stat::Agility)).max(1.0)
+	}
+
+	/// How far away interactibles can be used, in pixels.
+	pub fn reach(&self) -> f32 {
+		tileDiameter * (0.75 + 0.015 * self.bonus(Stat::Perception)).max(0.25)
+	}
+
+	/// Multiplier for damage dealt.
+	pub fn damage(&self) -> f32 {
+		(1.0 + 0.05 * self.bonus(Stat::Strength)).max(0.0)
+	}
+}
+
+#[derive(Clone, Debug, Component, Deserialize, Serialize)]
+pub struct Experience {
+	pub level: u32,
+	/// Progress towards the next level.
+	pub xp: u32,
+	/// Points gained from levels that haven't been spent on stats yet.
+	pub unspent: u32,
+}
+
+impl Default for Experience {
+	fn default() -> Self {
+		Self {
+			level: 1,
+			xp: 0,
+			unspent: 0,
+		}
+	}
+}
+
+impl Experience {
+	const pointsPerLevel: u32 = 3;
+
+	pub fn to_next_level(&self) -> u32 {
+		100 * self.level
+	}
+
+	/// Adds `xp`, levelling up as often as it's enough for. Returns the
+	/// number of levels gained.
+	pub fn gain(&mut self, xp: u32) -> u32 {
+		self.xp += xp;
+		let mut levels = 0;
+		while self.xp >= self.to_next_level() {
+			self.xp -= self.to_next_level();
+			self.level += 1;
+			self.unspent += Self::pointsPerLevel;
+			levels += 1;
+		}
+		levels
+	}
+}
+
+/// Map chunks of the current level the player has set foot in, so each
+/// grants exploration experience only once. Starts over with every level.
+#[derive(Clone, Debug, Default, Resource, Deserialize, Serialize)]
+pub struct VisitedChunks(pub HashSet<ChunkPos>);
+
+/// The character sheet. Up/Down pick a stat and Enter spends a point on it.
+#[derive(Default, Resource)]
+pub struct CharacterSheet {
+	pub open: bool,
+	selected: usize,
+}
+
+#[derive(Component)]
+struct CharacterSheetText;
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.init_resource::<CharacterSheet>();
+	app.add_systems(Startup, startup_gui);
+	app.add_systems(
+		Update,
+		(exploration_xp, spend_points, apply_stats, update_gui).chain(),
+	);
+	// deaths are sent in PostUpdate
+	app.add_systems(Last, kill_xp);
+}
+
+#[linkme::distributed_slice(crate::setupMap)]
+fn setup_map(map: &mut MutMap, cmd: &mut Commands, assets: &AssetServer) {
+	cmd.insert_resource(VisitedChunks::default());
+}
+
+#[linkme::distributed_slice(crate::save::serializers)]
+static serializer: Serializer = Serializer {
+	name: "exploration",
+	save: |world, dir| dir.write("exploration", world.resource::<VisitedChunks>()),
+	load: |dir| {
+		let visited: VisitedChunks = dir.read("exploration")?;
+		Ok(Box::new(move |world: &mut World| world.insert_resource(visited)))
+	},
+};
+
+fn grant_xp(xp: &mut Experience, amount: u32, isPlayer: bool) {
+	let levels = xp.gain(amount);
+	if isPlayer && levels > 0 {
+		FeedEntry::new(format!("You reached level {}!", xp.level))
+			.severity(Severity::Good)
+			.submit();
+	}
+}
+
+fn kill_xp(
+	mut deaths: EventReader<DeathEvent>,
+	victims: Query<&Health>,
+	mut killers: Query<(&mut Experience, Has<Player>)>,
+) {
+	for ev in &mut deaths {
+		let Some(Ok((mut xp, isPlayer))) = ev.source.map(|source| killers.get_mut(source)) else {
+			continue;
+		};
+		let amount = victims.get(ev.entity).map_or(10.0, |health| health.max() / 2.0);
+		grant_xp(&mut xp, amount as u32, isPlayer);
+	}
+}
+
+/// Grants experience for every map chunk the player sets foot in for the
+/// first time.
+fn exploration_xp(
+	visited: Option<ResMut<VisitedChunks>>,
+	mut player: Query<(&Transform, &mut Experience), With<Player>>,
+) {
+	const xpPerChunk: u32 = 10;
+
+	let Some(mut visited) = visited else {
+		return;
+	};
+
+	let Ok((transform, mut xp)) = player.get_single_mut() else {
+		return;
+	};
+	let pos = (transform.translation.xy() / tileRadius).round();
+	let chunk = ChunkPos::from(TilePos::of(pos.x as _, pos.y as _));
+	// the spawn chunk is free
+	let first = visited.0.is_empty();
+	if visited.0.insert(chunk) && !first {
+		grant_xp(&mut xp, xpPerChunk, true);
+	}
+}
+
+fn spend_points(
+	actions: Res<Input<Action>>,
+	bindings: Res<BindingsScreen>,
+	mut sheet: ResMut<CharacterSheet>,
+	mut player: Query<(&mut Stats, &mut Experience), With<Player>>,
+) {
+	if actions.just_pressed(Action::CharacterSheet) {
+		sheet.open = !sheet.open;
+	}
+	// the bindings screen uses the same keys
+	if !sheet.open || bindings.open {
+		return;
+	}
+
+	if actions.just_pressed(Action::MenuUp) {
+		sheet.selected = sheet.selected.checked_sub(1).unwrap_or(Stat::all.len() - 1);
+	}
+	if actions.just_pressed(Action::MenuDown) {
+		sheet.selected = (sheet.selected + 1) % Stat::all.len();
+	}
+	if actions.just_pressed(Action::MenuSelect) {
+		let (mut stats, mut xp) = player.single_mut();
+		if xp.unspent > 0 {
+			xp.unspent -= 1;
+			*stats.get_mut(Stat::all[sheet.selected]) += 1;
+		} else {
+			FeedEntry::new("No points left to spend.")
+				.category(FeedCategory::System)
+				.severity(Severity::Warning)
+				.submit();
+		}
+	}
+}
+
+/// Keeps max health in line with vitality.
+fn apply_stats(mut query: Query<(&Stats, &mut Health), Changed<Stats>>) {
+	for (stats, mut health) in &mut query {
+		let max = stats.max_health();
+		if health.max() != max {
+			health.set_max(max);
+		}
+	}
+}
+
+fn startup_gui(mut cmd: Commands, assets: Res<AssetServer>) {
+	cmd.spawn((
+		CharacterSheetText,
+		TextBundle {
+			text: Text::from_section(
+				"",
+				TextStyle {
+					font: assets.load("fonts/RedHatDisplay.ttf"),
+					font_size: 28.0,
+					color: Color::WHITE,
+				},
+			),
+			style: Style {
+				position_type: PositionType::Absolute,
+				top: Val::Percent(20.0),
+				right: Val::Percent(5.0),
+				padding: UiRect::all(Val::Px(10.0)),
+				..default()
+			},
+			background_color: BackgroundColor(Color::rgba(0.1, 0.1, 0.1, 0.9)),
+			visibility: Visibility::Hidden,
+			..default()
+		},
+	));
+}
+
+fn update_gui(
+	sheet: Res<CharacterSheet>,
+	player: Query<(&Stats, &Experience, &Health), With<Player>>,
+	mut text: Query<(&mut Text, &mut Visibility), With<CharacterSheetText>>,
+) {
+	let (mut text, mut visibility) = text.single_mut();
+	*visibility = if sheet.open {
+		Visibility::Inherited
+	} else {
+		Visibility::Hidden
+	};
+	let Ok((stats, xp, health)) = player.get_single() else {
+		return;
+	};
+	if !sheet.open {
+		return;
+	}
+
+	let mut value = String::new();
+	writeln!(value, "CHARACTER  Up/Down: select  Enter: spend point\n").unwrap();
+	writeln!(value, "Level {}  ({}/{} XP)", xp.level, xp.xp, xp.to_next_level()).unwrap();
+	writeln!(value, "Unspent points: {}\n", xp.unspent).unwrap();
+	for (i, &stat) in Stat::all.iter().enumerate() {
+		let marker = if i == sheet.selected { "> " } else { "  " };
+		writeln!(
+			value,
+			"{marker}{stat:?}: {}  ({})",
+			stats.get(stat),
+			stat.description()
+		)
+		.unwrap();
+	}
+	writeln!(value).unwrap();
+	writeln!(value, "Health: {:.0}/{:.0}", health.current(), health.max()).unwrap();
+	writeln!(value, "Speed: x{:.2}  Sprint: x{:.2}", stats.speed(), stats.sprint()).unwrap();
+	writeln!(value, "Reach: {:.1} tiles", stats.reach() / tileRadius).unwrap();
+	write!(value, "Damage: x{:.2}", stats.damage()).unwrap();
+
+	let section = &mut text.sections[0].value;
+	if *section != value {
+		*section = value;
+	}
+}
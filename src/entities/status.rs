@@ -0,0 +1,334 @@
+//! Timed status effects on anything with [`StatusEffects`]: damage and
+//! healing over time, movement speed changes and invulnerability. Lava floors
+//! and traps inflict them on whoever steps on them.
+
+use std::collections::HashMap;
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::health::{DamageEvent, DamageType, HealEvent};
+use super::player::Player;
+use crate::feed::IconAtlas;
+use crate::map::{tileRadius, FloorType, Landmark, Map, TilePos, TileType};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum StatusKind {
+	Poison,
+	Regeneration,
+	Burning,
+	Slow,
+	Haste,
+	Invulnerable,
+}
+
+/// What happens when an effect is applied to someone who already has it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stacking {
+	/// Restarts the duration, keeping the stronger magnitude.
+	Refresh,
+	/// Adds the durations up.
+	Extend,
+	/// Adds a stack, up to the given limit, and restarts the duration.
+	Intensify(u32),
+}
+
+impl StatusKind {
+	pub fn stacking(self) -> Stacking {
+		match self {
+			Self::Poison => Stacking::Intensify(5),
+			Self::Invulnerable => Stacking::Extend,
+			_ => Stacking::Refresh,
+		}
+	}
+
+	/// Seconds between ticks of effects that act periodically.
+	pub fn tick_interval(self) -> Option<f32> {
+		match self {
+			Self::Poison | Self::Regeneration => Some(1.0),
+			Self::Burning => Some(0.5),
+			_ => None,
+		}
+	}
+
+	/// Default strength: hit points per tick, or the speed multiplier.
+	pub fn magnitude(self) -> f32 {
+		match self {
+			Self::Poison => 2.0,
+			Self::Regeneration => 3.0,
+			Self::Burning => 2.5,
+			Self::Slow => 0.5,
+			Self::Haste => 1.5,
+			Self::Invulnerable => 0.0,
+		}
+	}
+
+	/// Misc atlas index of the HUD icon.
+	pub fn icon(self) -> u32 {
+		let landmark = match self {
+			Self::Poison => Landmark::ExplosionGreen,
+			Self::Regeneration => Landmark::ShrineIdol,
+			Self::Burning => Landmark::ShrineFlame,
+			Self::Slow => Landmark::TrapArrow,
+			Self::Haste => Landmark::PortalWormhole,
+			Self::Invulnerable => Landmark::ShrineCross,
+		};
+		landmark as u32
+	}
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StatusEffect {
+	pub kind: StatusKind,
+	/// Seconds left.
+	pub duration: f32,
+	pub magnitude: f32,
+	pub stacks: u32,
+	/// Whoever or whatever inflicted the effect, if anyone. Not saved, as
+	/// entities are respawned on load.
+	#[serde(skip)]
+	pub source: Option<Entity>,
+	/// Seconds since the last tick.
+	sinceTick: f32,
+}
+
+impl StatusEffect {
+	pub fn new(kind: StatusKind, duration: f32) -> Self {
+		Self {
+			kind,
+			duration,
+			magnitude: kind.magnitude(),
+			stacks: 1,
+			source: None,
+			sinceTick: 0.0,
+		}
+	}
+
+	pub fn with_magnitude(self, magnitude: f32) -> Self {
+		Self { magnitude, ..self }
+	}
+
+	pub fn with_source(self, source: Entity) -> Self {
+		Self {
+			source: Some(source),
+			..self
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, Component, Deserialize, Serialize)]
+pub struct StatusEffects(Vec<StatusEffect>);
+
+impl StatusEffects {
+	pub fn get(&self, kind: StatusKind) -> Option<&StatusEffect> {
+		self.0.iter().find(|effect| effect.kind == kind)
+	}
+
+	pub fn has(&self, kind: StatusKind) -> bool {
+		self.get(kind).is_some()
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &StatusEffect> {
+		self.0.iter()
+	}
+
+	/// Adds `effect`, combining it with one of the same kind according to its
+	/// [`Stacking`].
+	pub fn apply(&mut self, effect: StatusEffect) {
+		let Some(existing) = self.0.iter_mut().find(|other| other.kind == effect.kind) else {
+			self.0.push(effect);
+			return;
+		};
+		match effect.kind.stacking() {
+			Stacking::Refresh => {
+				existing.duration = existing.duration.max(effect.duration);
+				existing.magnitude = existing.magnitude.max(effect.magnitude);
+				existing.source = effect.source.or(existing.source);
+			},
+			Stacking::Extend => existing.duration += effect.duration,
+			Stacking::Intensify(maxStacks) => {
+				existing.stacks = (existing.stacks + effect.stacks).min(maxStacks);
+				existing.duration = existing.duration.max(effect.duration);
+				existing.source = effect.source.or(existing.source);
+			},
+		}
+	}
+
+	pub fn remove(&mut self, kind: StatusKind) {
+		self.0.retain(|effect| effect.kind != kind);
+	}
+
+	/// Multiplier for movement speed from slow and haste.
+	pub fn speed_multiplier(&self) -> f32 {
+		[StatusKind::Slow, StatusKind::Haste]
+			.into_iter()
+			.filter_map(|kind| self.get(kind))
+			.map(|effect| effect.magnitude)
+			.product()
+	}
+}
+
+#[derive(Component)]
+struct StatusBar;
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.add_systems(Startup, startup_gui);
+	app.add_systems(Update, (hazards, tick_effects, update_gui).chain());
+}
+
+fn startup_gui(mut cmd: Commands) {
+	// just above the health bar
+	cmd.spawn((
+		StatusBar,
+		NodeBundle {
+			style: Style {
+				position_type: PositionType::Absolute,
+				bottom: Val::Px(65.0),
+				left: Val::Px(10.0),
+				column_gap: Val::Px(4.0),
+				..default()
+			},
+			..default()
+		},
+	));
+}
+
+/// Applies effects of the tiles actors stand on: lava burns for as long as
+/// they stay on it, traps trigger when stepped on.
+fn hazards(
+	map: Option<Res<Map>>,
+	mut actors: Query<(Entity, &Transform, &mut StatusEffects)>,
+	mut lastTiles: Local<HashMap<Entity, TilePos>>,
+) {
+	let Some(map) = map else {
+		return;
+	};
+	// forget despawned actors
+	lastTiles.retain(|&ent, _| actors.contains(ent));
+
+	for (ent, transform, mut effects) in &mut actors {
+		let pos = (transform.translation.xy() / tileRadius).round();
+		let pos = TilePos::of(pos.x as _, pos.y as _);
+		let entered = lastTiles.insert(ent, pos) != Some(pos);
+
+		let pair = &map[pos];
+		if let TileType::Floor(FloorType::LavaRed | FloorType::LavaBlue | FloorType::LavaCyan) =
+			pair.background.ty
+		{
+			effects.apply(StatusEffect::new(StatusKind::Burning, 2.0));
+		}
+
+		let TileType::Landmark { ty, .. } = pair.foreground.ty else {
+			continue;
+		};
+		let effect = match ty {
+			Landmark::TrapArrow => StatusEffect::new(StatusKind::Slow, 3.0),
+			Landmark::TrapPentagram => StatusEffect::new(StatusKind::Burning, 4.0),
+			Landmark::TrapSkull => StatusEffect::new(StatusKind::Poison, 8.0),
+			_ => continue,
+		};
+		if entered {
+			effects.apply(effect);
+		}
+	}
+}
+
+fn tick_effects(
+	time: Res<Time>,
+	mut actors: Query<(Entity, &mut StatusEffects)>,
+	mut damages: EventWriter<DamageEvent>,
+	mut heals: EventWriter<HealEvent>,
+) {
+	let dt = time.delta_seconds();
+	for (target, mut effects) in &mut actors {
+		for effect in &mut effects.0 {
+			effect.duration -= dt;
+			let Some(interval) = effect.kind.tick_interval() else {
+				continue;
+			};
+
+			effect.sinceTick += dt;
+			while effect.sinceTick >= interval {
+				effect.sinceTick -= interval;
+				let amount = effect.magnitude * effect.stacks as f32;
+				let source = effect.source;
+				let ty = match effect.kind {
+					StatusKind::Regeneration => {
+						heals.send(HealEvent {
+							target,
+							source,
+							amount,
+						});
+						continue;
+					},
+					StatusKind::Burning => DamageType::Fire,
+					_ => DamageType::Poison,
+				};
+				damages.send(DamageEvent {
+					target,
+					source,
+					amount,
+					ty,
+				});
+			}
+		}
+		effects.0.retain(|effect| effect.duration > 0.0);
+	}
+}
+
+fn update_gui(
+	mut cmd: Commands,
+	icons: Res<IconAtlas>,
+	assets: Res<AssetServer>,
+	player: Query<&StatusEffects, With<Player>>,
+	bar: Query<Entity, With<StatusBar>>,
+	mut shown: Local<Vec<(StatusKind, u32)>>,
+) {
+	const iconSize: f32 = 32.0;
+
+	let Ok(effects) = player.get_single() else {
+		return;
+	};
+	let current: Vec<_> = effects.iter().map(|effect| (effect.kind, effect.stacks)).collect();
+	if *shown == current {
+		return;
+	}
+
+	let bar = bar.single();
+	cmd.entity(bar).despawn_descendants();
+	for &(kind, stacks) in &current {
+		let icon = cmd
+			.spawn(AtlasImageBundle {
+				style: Style {
+					width: Val::Px(iconSize),
+					height: Val::Px(iconSize),
+					align_items: AlignItems::End,
+					justify_content: JustifyContent::End,
+					..default()
+				},
+				texture_atlas: icons.0.clone(),
+				texture_atlas_image: UiTextureAtlasImage {
+					index: kind.icon() as _,
+					..default()
+				},
+				..default()
+			})
+			.with_children(|icon| {
+				if stacks > 1 {
+					icon.spawn(TextBundle::from_section(
+						stacks.to_string(),
+						TextStyle {
+							font: assets.load("fonts/RedHatDisplay.ttf"),
+							font_size: 16.0,
+							color: Color::WHITE,
+						},
+					));
+				}
+			})
+			.id();
+		cmd.entity(bar).add_child(icon);
+	}
+	*shown = current;
+}
@@ -0,0 +1,442 @@
+//! The message feed. Any system or thread can post a [`FeedEntry`], usually
+//! through [`print_feed!`]; recent entries show in the top left corner and
+//! fade out, and the full history opens in a scrollable panel (H by default).
+
+use std::fmt::Write;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::input::Action;
+use crate::map::{tileDiameter, Landmark};
+use crate::save::Serializer;
+use crate::AResult;
+
+/// Entries posted since the feed last picked them up.
+static pending: Mutex<Vec<FeedEntry>> = Mutex::new(Vec::new());
+
+fn pending_entries() -> MutexGuard<'static, Vec<FeedEntry>> {
+	// entries are plain data, so a panic while holding the lock can't leave them inconsistent
+	pending.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum FeedCategory {
+	#[default]
+	General,
+	Interaction,
+	Combat,
+	/// Messages about the game itself rather than the world, like failing to
+	/// save settings.
+	System,
+}
+
+#[derive(
+	Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize,
+)]
+pub enum Severity {
+	#[default]
+	Info,
+	Good,
+	Warning,
+	Bad,
+}
+
+impl Severity {
+	pub fn color(self) -> Color {
+		match self {
+			Self::Info => Color::WHITE,
+			Self::Good => Color::rgb(0.5, 1.0, 0.5),
+			Self::Warning => Color::rgb(1.0, 0.85, 0.3),
+			Self::Bad => Color::rgb(1.0, 0.4, 0.35),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FeedEntry {
+	pub text: String,
+	pub category: FeedCategory,
+	pub severity: Severity,
+	/// Text color; the severity's color unless overridden.
+	pub color: Color,
+	/// Index into the misc tile atlas of an icon shown before the text.
+	pub icon: Option<u32>,
+	/// Game time in seconds the entry was posted at. Filled in when the feed
+	/// picks it up.
+	pub time: f32,
+}
+
+impl FeedEntry {
+	pub fn new(text: impl Into<String>) -> Self {
+		Self {
+			text: text.into(),
+			category: default(),
+			severity: default(),
+			color: Severity::default().color(),
+			icon: None,
+			time: 0.0,
+		}
+	}
+
+	pub fn category(mut self, category: FeedCategory) -> Self {
+		self.category = category;
+		self
+	}
+
+	/// Sets the severity, and the color to match it.
+	pub fn severity(mut self, severity: Severity) -> Self {
+		self.severity = severity;
+		self.color = severity.color();
+		self
+	}
+
+	pub fn color(mut self, color: Color) -> Self {
+		self.color = color;
+		self
+	}
+
+	/// Uses the sprite of `landmark` as icon.
+	pub fn icon(mut self, landmark: Landmark) -> Self {
+		self.icon = Some(landmark as u32);
+		self
+	}
+
+	/// Posts the entry. Can be called from anywhere; it shows up on the next
+	/// frame.
+	pub fn submit(self) {
+		pending_entries().push(self);
+	}
+}
+
+/// Posts a formatted message to the feed as a general entry. Use
+/// [`FeedEntry`] directly to set a category, severity or icon.
+#[macro_export]
+macro_rules! print_feed {
+	($($args:tt)*) => {
+		$crate::feed::FeedEntry::new(format!($($args)*)).submit()
+	};
+}
+
+/// Every entry posted so far, oldest first, up to a limit.
+#[derive(Clone, Debug, Default, Resource)]
+pub struct FeedHistory(pub Vec<FeedEntry>);
+
+#[linkme::distributed_slice(crate::save::serializers)]
+static serializer: Serializer = Serializer {
+	name: "feed",
+	save: |world, dir| dir.write("feed", &world.resource::<FeedHistory>().0),
+	load: |dir| {
+		let history = FeedHistory(dir.read("feed")?);
+		Ok(Box::new(move |world: &mut World| world.insert_resource(history)))
+	},
+};
+
+/// The history panel. The mouse wheel scrolls it while it's open.
+#[derive(Default, Resource)]
+pub struct HistoryPanel {
+	pub open: bool,
+	/// Distance scrolled up from the newest entries, in pixels.
+	scroll: f32,
+}
+
+/// Atlas of the misc tiles, for icons in the UI.
+#[derive(Resource)]
+pub struct IconAtlas(pub Handle<TextureAtlas>);
+
+#[derive(Component)]
+struct FeedBox;
+
+/// A recent entry in the feed box, posted at the given time.
+#[derive(Clone, Copy, Component)]
+struct FeedLine(f32);
+
+#[derive(Component)]
+struct HistoryNode;
+
+#[derive(Component)]
+struct HistoryText;
+
+const fontPath: &str = "fonts/RedHatDisplay.ttf";
+const fontSize: f32 = 24.0;
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.init_resource::<FeedHistory>();
+	app.init_resource::<HistoryPanel>();
+	app.add_systems(Startup, startup_gui);
+	app.add_systems(
+		Update,
+		(
+			(collect_entries, fade_lines).chain(),
+			(toggle_history, scroll_history, update_history).chain(),
+		),
+	);
+}
+
+fn startup_gui(
+	mut cmd: Commands,
+	assets: Res<AssetServer>,
+	mut atlases: ResMut<Assets<TextureAtlas>>,
+) {
+	let atlas = TextureAtlas::from_grid(
+		assets.load("tiles/misc.png"),
+		Vec2::splat(tileDiameter),
+		16,
+		12,
+		None,
+		None,
+	);
+	cmd.insert_resource(IconAtlas(atlases.add(atlas)));
+
+	cmd.spawn((
+		FeedBox,
+		NodeBundle {
+			style: Style {
+				max_width: Val::Vw(100.0),
+				padding: UiRect::all(Val::Px(5.0)),
+				flex_direction: FlexDirection::Column,
+				position_type: PositionType::Absolute,
+				top: Val::Percent(0.0),
+				left: Val::Percent(0.0),
+				..default()
+			},
+			background_color: BackgroundColor(Color::rgba(0.25, 0.25, 0.25, 0.75)),
+			..default()
+		},
+	));
+
+	cmd.spawn((
+		HistoryNode,
+		NodeBundle {
+			style: Style {
+				position_type: PositionType::Absolute,
+				top: Val::Percent(15.0),
+				left: Val::Percent(20.0),
+				width: Val::Percent(60.0),
+				height: Val::Percent(70.0),
+				flex_direction: FlexDirection::Column,
+				overflow: Overflow::clip(),
+				..default()
+			},
+			background_color: BackgroundColor(Color::rgba(0.1, 0.1, 0.1, 0.9)),
+			visibility: Visibility::Hidden,
+			..default()
+		},
+	))
+	.with_children(|panel| {
+		panel.spawn((
+			HistoryText,
+			TextBundle {
+				style: Style {
+					padding: UiRect::all(Val::Px(10.0)),
+					..default()
+				},
+				..default()
+			},
+		));
+	});
+}
+
+fn collect_entries(
+	mut cmd: Commands,
+	assets: Res<AssetServer>,
+	icons: Res<IconAtlas>,
+	time: Res<Time>,
+	mut history: ResMut<FeedHistory>,
+	feedBox: Query<Entity, With<FeedBox>>,
+	lines: Query<(Entity, &FeedLine)>,
+) {
+	const maxLines: usize = 10;
+	const historyLimit: usize = 500;
+
+	let entries = std::mem::take(&mut *pending_entries());
+	if entries.is_empty() {
+		return;
+	}
+
+	let feedBox = feedBox.single();
+	let now = time.elapsed_seconds();
+	let font = assets.load(fontPath);
+	// only the newest entries fit into the box
+	let shown = entries.len().min(maxLines);
+	let hidden = entries.len() - shown;
+	for (i, mut entry) in entries.into_iter().enumerate() {
+		entry.time = now;
+		if i < hidden {
+			history.0.push(entry);
+			continue;
+		}
+
+		let line = cmd
+			.spawn((
+				FeedLine(now),
+				NodeBundle {
+					style: Style {
+						align_items: AlignItems::Center,
+						column_gap: Val::Px(4.0),
+						..default()
+					},
+					..default()
+				},
+			))
+			.with_children(|line| {
+				if let Some(index) = entry.icon {
+					line.spawn(AtlasImageBundle {
+						style: Style {
+							width: Val::Px(fontSize),
+							height: Val::Px(fontSize),
+							..default()
+						},
+						texture_atlas: icons.0.clone(),
+						texture_atlas_image: UiTextureAtlasImage {
+							index: index as _,
+							..default()
+						},
+						..default()
+					});
+				}
+				line.spawn(TextBundle::from_section(
+					entry.text.clone(),
+					TextStyle {
+						font: font.clone(),
+						font_size: fontSize,
+						color: entry.color,
+					},
+				));
+			})
+			.id();
+		cmd.entity(feedBox).add_child(line);
+		history.0.push(entry);
+	}
+
+	// make room by dropping the oldest lines; the new ones aren't in the query yet
+	let mut lines: Vec<_> = lines.iter().collect();
+	lines.sort_by(|(_, a), (_, b)| a.0.total_cmp(&b.0));
+	let excess = (lines.len() + shown).saturating_sub(maxLines);
+	for &(ent, _) in &lines[.. excess] {
+		cmd.entity(ent).despawn_recursive();
+	}
+
+	let len = history.0.len();
+	if len > historyLimit {
+		history.0.drain(.. len - historyLimit);
+	}
+}
+
+/// Fades out lines in the feed box once they're old enough, and removes them
+/// when they're gone.
+fn fade_lines(
+	mut cmd: Commands,
+	time: Res<Time>,
+	lines: Query<(Entity, &FeedLine, &Children)>,
+	mut texts: Query<&mut Text>,
+	mut images: Query<&mut BackgroundColor, With<UiTextureAtlasImage>>,
+) {
+	const fadeAfter: f32 = 8.0;
+	const fadeTime: f32 = 2.0;
+
+	let now = time.elapsed_seconds();
+	for (ent, line, children) in &lines {
+		let age = now - line.0;
+		if age < fadeAfter {
+			continue;
+		}
+		let alpha = 1.0 - (age - fadeAfter) / fadeTime;
+		if alpha <= 0.0 {
+			cmd.entity(ent).despawn_recursive();
+			continue;
+		}
+
+		for &child in children {
+			if let Ok(mut text) = texts.get_mut(child) {
+				for section in &mut text.sections {
+					section.style.color.set_a(alpha);
+				}
+			}
+			if let Ok(mut color) = images.get_mut(child) {
+				color.0.set_a(alpha);
+			}
+		}
+	}
+}
+
+fn toggle_history(
+	actions: Res<Input<Action>>,
+	mut panel: ResMut<HistoryPanel>,
+	mut node: Query<&mut Visibility, With<HistoryNode>>,
+) {
+	if !actions.just_pressed(Action::FeedHistory) {
+		return;
+	}
+	panel.open = !panel.open;
+	panel.scroll = 0.0;
+	*node.single_mut() = if panel.open {
+		Visibility::Inherited
+	} else {
+		Visibility::Hidden
+	};
+}
+
+fn scroll_history(
+	mut mouseWheel: EventReader<MouseWheel>,
+	mut panel: ResMut<HistoryPanel>,
+	node: Query<&Node, With<HistoryNode>>,
+	mut text: Query<(&Node, &mut Style), With<HistoryText>>,
+) {
+	let mut delta = 0.0;
+	for ev in &mut mouseWheel {
+		delta += match ev.unit {
+			MouseScrollUnit::Line => ev.y * fontSize,
+			MouseScrollUnit::Pixel => ev.y,
+		};
+	}
+	if !panel.open {
+		return;
+	}
+
+	// keep the newest entries at the bottom of the panel
+	let (textNode, mut style) = text.single_mut();
+	let maxScroll = (textNode.size().y - node.single().size().y).max(0.0);
+	let scroll = (panel.scroll + delta).clamp(0.0, maxScroll);
+	if panel.scroll != scroll {
+		panel.scroll = scroll;
+	}
+	let top = Val::Px(scroll - maxScroll);
+	if style.top != top {
+		style.top = top;
+	}
+}
+
+fn update_history(
+	assets: Res<AssetServer>,
+	history: Res<FeedHistory>,
+	mut text: Query<&mut Text, With<HistoryText>>,
+) {
+	if !history.is_changed() {
+		return;
+	}
+
+	let font = assets.load(fontPath);
+	let sections = &mut text.single_mut().sections;
+	sections.clear();
+	for entry in &history.0 {
+		let secs = entry.time as u32;
+		let mut value = format!("[{:02}:{:02}] ", secs / 60, secs % 60);
+		if entry.category != FeedCategory::General {
+			write!(value, "{:?}: ", entry.category).unwrap();
+		}
+		value.push_str(&entry.text);
+		value.push('\n');
+		sections.push(TextSection::new(
+			value,
+			TextStyle {
+				font: font.clone(),
+				font_size: fontSize,
+				color: entry.color,
+			},
+		));
+	}
+}
@@ -0,0 +1,440 @@
+//! Action-based input. Gameplay reads [`Input<Action>`] and [`MoveAxis`]
+//! instead of devices directly; which keys, mouse buttons and gamepad
+//! buttons trigger an action is configured in `assets/bindings.ron`, and can
+//! be overridden in game on the bindings screen (F1 by default).
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use bevy::input::InputSystem;
+use bevy::math::vec2;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::feed::{FeedCategory, FeedEntry, Severity};
+use crate::map::load_ron_blocking;
+use crate::menu::GameState;
+use crate::settings::UserConfig;
+use crate::AResult;
+
+/// Where the shipped [`Bindings`] are loaded from, relative to the assets
+/// directory. Rebinds are saved under the same name in the [`UserConfig`]
+/// directory.
+const bindingsPath: &str = "bindings.ron";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub enum Action {
+	MoveUp,
+	MoveDown,
+	MoveLeft,
+	MoveRight,
+	Sprint,
+	Interact,
+	/// Uses the interactible under the mouse, or walks to the clicked tile.
+	Click,
+	Attack,
+	Examine,
+	ZoomIn,
+	ZoomOut,
+	/// Debug builds only.
+	SavePosition,
+	/// Debug builds only.
+	RecallPosition,
+	/// Toggles physics debug rendering in debug builds.
+	ToggleDebug,
+	/// Detaches the camera from the player in debug builds.
+	FreeCamera,
+	/// Opens and closes the message history.
+	FeedHistory,
+	/// Opens and closes the character sheet.
+	CharacterSheet,
+	/// Saves the game to the quicksave slot.
+	QuickSave,
+	/// Loads the quicksave.
+	QuickLoad,
+	/// Opens and closes the bindings screen.
+	Bindings,
+	/// Pauses the game, and goes back in menus.
+	Pause,
+	/// Moves the selection in menus, on the character sheet and on the
+	/// bindings screen.
+	MenuUp,
+	MenuDown,
+	/// Changes the selected setting.
+	MenuLeft,
+	MenuRight,
+	/// Activates the selected menu item.
+	MenuSelect,
+}
+
+impl Action {
+	pub const all: &'static [Self] = &[
+		Self::MoveUp,
+		Self::MoveDown,
+		Self::MoveLeft,
+		Self::MoveRight,
+		Self::Sprint,
+		Self::Interact,
+		Self::Click,
+		Self::Attack,
+		Self::Examine,
+		Self::ZoomIn,
+		Self::ZoomOut,
+		Self::SavePosition,
+		Self::RecallPosition,
+		Self::ToggleDebug,
+		Self::FreeCamera,
+		Self::FeedHistory,
+		Self::CharacterSheet,
+		Self::QuickSave,
+		Self::QuickLoad,
+		Self::Bindings,
+		Self::Pause,
+		Self::MenuUp,
+		Self::MenuDown,
+		Self::MenuLeft,
+		Self::MenuRight,
+		Self::MenuSelect,
+	];
+
+	/// Whether the action works outside of gameplay, in menus and on the
+	/// bindings screen.
+	pub fn is_menu(self) -> bool {
+		matches!(
+			self,
+			Self::Bindings |
+				Self::Pause |
+				Self::MenuUp |
+				Self::MenuDown |
+				Self::MenuLeft |
+				Self::MenuRight |
+				Self::MenuSelect
+		)
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Binding {
+	Key(KeyCode),
+	Mouse(MouseButton),
+	Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+	fn is_gamepad(self) -> bool {
+		matches!(self, Self::Gamepad(_))
+	}
+}
+
+#[derive(Clone, Debug, Resource, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Bindings {
+	pub actions: BTreeMap<Action, Vec<Binding>>,
+	/// How far the left stick has to be pushed before it moves the player,
+	/// from 0 to 1.
+	pub deadZone: f32,
+}
+
+impl Default for Bindings {
+	fn default() -> Self {
+		use GamepadButtonType as Pad;
+		let (key, pad) = (Binding::Key, Binding::Gamepad);
+		Self {
+			actions: BTreeMap::from([
+				(Action::MoveUp, vec![key(KeyCode::W), pad(Pad::DPadUp)]),
+				(Action::MoveDown, vec![key(KeyCode::S), pad(Pad::DPadDown)]),
+				(Action::MoveLeft, vec![key(KeyCode::A), pad(Pad::DPadLeft)]),
+				(Action::MoveRight, vec![key(KeyCode::D), pad(Pad::DPadRight)]),
+				(Action::Sprint, vec![key(KeyCode::ShiftLeft), pad(Pad::LeftTrigger2)]),
+				(Action::Interact, vec![key(KeyCode::E), pad(Pad::South)]),
+				(Action::Click, vec![Binding::Mouse(MouseButton::Left)]),
+				(Action::Attack, vec![key(KeyCode::F), pad(Pad::West)]),
+				(
+					Action::Examine,
+					vec![key(KeyCode::Q), Binding::Mouse(MouseButton::Right), pad(Pad::North)],
+				),
+				(Action::ZoomIn, vec![key(KeyCode::Equals), pad(Pad::RightTrigger)]),
+				(Action::ZoomOut, vec![key(KeyCode::Minus), pad(Pad::LeftTrigger)]),
+				(Action::SavePosition, vec![key(KeyCode::Y)]),
+				(Action::RecallPosition, vec![key(KeyCode::T)]),
+				(Action::ToggleDebug, vec![key(KeyCode::F11)]),
+				(Action::FreeCamera, vec![key(KeyCode::F10)]),
+				(Action::FeedHistory, vec![key(KeyCode::H)]),
+				(Action::CharacterSheet, vec![key(KeyCode::C), pad(Pad::RightThumb)]),
+				(Action::QuickSave, vec![key(KeyCode::F5)]),
+				(Action::QuickLoad, vec![key(KeyCode::F9)]),
+				(Action::Bindings, vec![key(KeyCode::F1), pad(Pad::Select)]),
+				(Action::Pause, vec![key(KeyCode::Escape), pad(Pad::Start)]),
+				(Action::MenuUp, vec![key(KeyCode::Up), pad(Pad::DPadUp)]),
+				(Action::MenuDown, vec![key(KeyCode::Down), pad(Pad::DPadDown)]),
+				(Action::MenuLeft, vec![key(KeyCode::Left), pad(Pad::DPadLeft)]),
+				(Action::MenuRight, vec![key(KeyCode::Right), pad(Pad::DPadRight)]),
+				(Action::MenuSelect, vec![key(KeyCode::Return), pad(Pad::South)]),
+			]),
+			deadZone: 0.2,
+		}
+	}
+}
+
+impl Bindings {
+	/// Loads the shipped bindings, with the actions the player rebound
+	/// replacing theirs.
+	pub fn load_blocking(assets: &AssetServer, config: &UserConfig) -> AResult<Self> {
+		let mut bindings: Self = load_ron_blocking(assets, bindingsPath)?;
+		if let Some(overrides) = config.load_blocking::<Self>(bindingsPath)? {
+			bindings.actions.extend(overrides.actions);
+			bindings.deadZone = overrides.deadZone;
+		}
+		Ok(bindings)
+	}
+
+	pub fn save_blocking(&self, config: &UserConfig) -> AResult<()> {
+		config.save_blocking(bindingsPath, self)
+	}
+}
+
+/// Direction the player wants to move in, combined from the movement actions
+/// and the left stick. `x` points right and `y` up; never longer than 1.
+#[derive(Clone, Copy, Debug, Default, Resource)]
+pub struct MoveAxis(pub Vec2);
+
+/// Lists every action with its bindings. Arrow keys pick an action, Enter
+/// rebinds it to the next key or button pressed (replacing the bindings of
+/// the same device) and Backspace clears it; Escape cancels a rebind.
+/// Gameplay actions are ignored while the screen is open, and every action
+/// while it waits for a key.
+#[derive(Default, Resource)]
+pub struct BindingsScreen {
+	pub open: bool,
+	selected: usize,
+	listening: bool,
+}
+
+#[derive(Component)]
+struct BindingsText;
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.init_resource::<Input<Action>>();
+	app.init_resource::<MoveAxis>();
+	app.init_resource::<BindingsScreen>();
+	app.add_systems(Startup, (load_bindings, startup_gui));
+	app.add_systems(PreUpdate, update_actions.after(InputSystem));
+	app.add_systems(Update, (rebind, update_gui).chain());
+}
+
+fn load_bindings(mut cmd: Commands, assets: Res<AssetServer>, config: Res<UserConfig>) {
+	let bindings = Bindings::load_blocking(&assets, &config).unwrap_or_else(|err| {
+		warn!("using default bindings, could not load {bindingsPath}: {err:#}");
+		default()
+	});
+	cmd.insert_resource(bindings);
+}
+
+fn update_actions(
+	bindings: Option<Res<Bindings>>,
+	screen: Res<BindingsScreen>,
+	state: Res<State<GameState>>,
+	keyboard: Res<Input<KeyCode>>,
+	mouse: Res<Input<MouseButton>>,
+	gamepads: Res<Gamepads>,
+	buttons: Res<Input<GamepadButton>>,
+	axes: Res<Axis<GamepadAxis>>,
+	mut actions: ResMut<Input<Action>>,
+	mut moveAxis: ResMut<MoveAxis>,
+) {
+	let Some(bindings) = bindings else {
+		return;
+	};
+	let gamepad = gamepads.iter().next();
+	let pressed = |binding: &Binding| match *binding {
+		Binding::Key(key) => keyboard.pressed(key),
+		Binding::Mouse(button) => mouse.pressed(button),
+		Binding::Gamepad(ty) => {
+			gamepad.is_some_and(|gamepad| buttons.pressed(GamepadButton::new(gamepad, ty)))
+		},
+	};
+
+	// gameplay stops in menus and while the bindings screen is open
+	let playing = *state.get() == GameState::Playing && !screen.open;
+	actions.clear();
+	for &action in Action::all {
+		let usable = playing || action.is_menu();
+		let bound = bindings.actions.get(&action);
+		if usable && bound.is_some_and(|bound| bound.iter().any(pressed)) {
+			actions.press(action);
+			// keys pressed to rebind, or to cancel it, shouldn't act as well
+			if screen.listening {
+				actions.clear_just_pressed(action);
+			}
+		} else {
+			actions.release(action);
+		}
+	}
+
+	let axis = |positive, negative| {
+		actions.pressed(positive) as i32 as f32 - actions.pressed(negative) as i32 as f32
+	};
+	let digital = vec2(
+		axis(Action::MoveRight, Action::MoveLeft),
+		axis(Action::MoveUp, Action::MoveDown),
+	);
+	let stick = gamepad
+		.filter(|_| playing)
+		.map(|gamepad| {
+			let get = |ty| axes.get(GamepadAxis::new(gamepad, ty)).unwrap_or(0.0);
+			vec2(
+				get(GamepadAxisType::LeftStickX),
+				get(GamepadAxisType::LeftStickY),
+			)
+		})
+		.filter(|stick| stick.length() >= bindings.deadZone)
+		.unwrap_or_default();
+	moveAxis.0 = (digital.normalize_or_zero() + stick).clamp_length_max(1.0);
+}
+
+fn rebind(
+	actions: Res<Input<Action>>,
+	keyboard: Res<Input<KeyCode>>,
+	mouse: Res<Input<MouseButton>>,
+	buttons: Res<Input<GamepadButton>>,
+	config: Res<UserConfig>,
+	bindings: Option<ResMut<Bindings>>,
+	mut screen: ResMut<BindingsScreen>,
+) {
+	let Some(mut bindings) = bindings else {
+		return;
+	};
+
+	if screen.open && screen.listening {
+		if keyboard.just_pressed(KeyCode::Escape) {
+			screen.listening = false;
+			return;
+		}
+		let pressed = keyboard
+			.get_just_pressed()
+			.map(|&key| Binding::Key(key))
+			.chain(mouse.get_just_pressed().map(|&button| Binding::Mouse(button)))
+			.chain(
+				buttons
+					.get_just_pressed()
+					.map(|button| Binding::Gamepad(button.button_type)),
+			)
+			.next();
+		let Some(binding) = pressed else {
+			return;
+		};
+
+		let action = Action::all[screen.selected];
+		let bound = bindings.actions.entry(action).or_default();
+		bound.retain(|b| b.is_gamepad() != binding.is_gamepad());
+		bound.push(binding);
+		screen.listening = false;
+		if let Err(err) = bindings.save_blocking(&config) {
+			FeedEntry::new(format!("Could not save bindings: {err}"))
+				.category(FeedCategory::System)
+				.severity(Severity::Warning)
+				.submit();
+		}
+		return;
+	}
+
+	if actions.just_pressed(Action::Bindings) {
+		screen.open = !screen.open;
+		screen.listening = false;
+		return;
+	}
+	if !screen.open {
+		return;
+	}
+
+	let action = Action::all[screen.selected];
+	if actions.just_pressed(Action::MenuUp) {
+		screen.selected = screen.selected.checked_sub(1).unwrap_or(Action::all.len() - 1);
+	}
+	if actions.just_pressed(Action::MenuDown) {
+		screen.selected = (screen.selected + 1) % Action::all.len();
+	}
+	if actions.just_pressed(Action::MenuSelect) {
+		screen.listening = true;
+	}
+	if keyboard.just_pressed(KeyCode::Back) {
+		// kept empty, so the shipped bindings don't come back on load
+		bindings.actions.insert(action, vec![]);
+		if let Err(err) = bindings.save_blocking(&config) {
+			FeedEntry::new(format!("Could not save bindings: {err}"))
+				.category(FeedCategory::System)
+				.severity(Severity::Warning)
+				.submit();
+		}
+	}
+}
+
+fn startup_gui(mut cmd: Commands, assets: Res<AssetServer>) {
+	cmd.spawn((
+		BindingsText,
+		TextBundle {
+			text: Text::from_section(
+				"",
+				TextStyle {
+					font: assets.load("fonts/RedHatDisplay.ttf"),
+					font_size: 32.0,
+					color: Color::WHITE,
+				},
+			),
+			style: Style {
+				position_type: PositionType::Absolute,
+				top: Val::Percent(20.0),
+				left: Val::Percent(35.0),
+				padding: UiRect::all(Val::Px(10.0)),
+				..default()
+			},
+			background_color: BackgroundColor(Color::rgba(0.1, 0.1, 0.1, 0.9)),
+			visibility: Visibility::Hidden,
+			..default()
+		},
+	));
+}
+
+fn update_gui(
+	screen: Res<BindingsScreen>,
+	bindings: Option<Res<Bindings>>,
+	mut text: Query<(&mut Text, &mut Visibility), With<BindingsText>>,
+) {
+	let Some(bindings) = bindings else {
+		return;
+	};
+	if !screen.is_changed() && !bindings.is_changed() {
+		return;
+	}
+
+	let (mut text, mut visibility) = text.single_mut();
+	*visibility = if screen.open {
+		Visibility::Inherited
+	} else {
+		Visibility::Hidden
+	};
+
+	let text = &mut text.sections[0].value;
+	text.clear();
+	text.push_str("BINDINGS  Up/Down: select  Enter: rebind  Escape: cancel  Backspace: clear\n\n");
+	for (i, action) in Action::all.iter().enumerate() {
+		let marker = if i != screen.selected {
+			"  "
+		} else if screen.listening {
+			"? "
+		} else {
+			"> "
+		};
+		write!(text, "{marker}{action:?}:").unwrap();
+		for binding in bindings.actions.get(action).into_iter().flatten() {
+			match binding {
+				Binding::Key(key) => write!(text, "  {key:?}"),
+				Binding::Mouse(button) => write!(text, "  Mouse {button:?}"),
+				Binding::Gamepad(button) => write!(text, "  Pad {button:?}"),
+			}
+			.unwrap();
+		}
+		text.push('\n');
+	}
+}
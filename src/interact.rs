@@ -0,0 +1,329 @@
+//! Interaction. Handlers read [`InteractEvent`]s in [`InteractSet::Handle`]
+//! and answer each event they take care of with an [`InteractResult`]; events
+//! nobody answers are reported as having no effect.
+//!
+//! The [`Interactible`] nearest to the mouse cursor is highlighted and named
+//! in a tooltip; clicking it uses it, walking over first if it's out of reach,
+//! and clicking a floor tile walks there.
+
+use std::collections::HashSet;
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::editor::Editor;
+use crate::feed::{FeedCategory, FeedEntry, Severity};
+use crate::entities::door::Door;
+use crate::entities::player::{move_cursor, Cursor, Player, WalkPath};
+use crate::entities::stats::Stats;
+use crate::input::Action;
+use crate::map::{tileRadius, Map, PluckedTile, TilePos};
+use crate::{find_interactible_entities, Interactible, IsoSprite};
+
+/// Tint of the hovered interactible's sprite.
+const highlightColor: Color = Color::rgb(1.5, 1.5, 1.2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InteractKind {
+	Use,
+	Attack,
+	Examine,
+}
+
+/// `source` does something to `target`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Event)]
+pub struct InteractEvent {
+	pub kind: InteractKind,
+	pub source: Entity,
+	pub target: Entity,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InteractOutcome {
+	Done,
+	/// The interaction was understood but didn't work out, for the given
+	/// reason.
+	Failed(String),
+}
+
+/// Sent by the handler that took care of an [`InteractEvent`].
+#[derive(Clone, Debug, Event)]
+pub struct InteractResult {
+	pub event: InteractEvent,
+	pub outcome: InteractOutcome,
+}
+
+impl InteractResult {
+	pub fn done(event: InteractEvent) -> Self {
+		Self {
+			event,
+			outcome: InteractOutcome::Done,
+		}
+	}
+
+	pub fn failed(event: InteractEvent, reason: impl Into<String>) -> Self {
+		Self {
+			event,
+			outcome: InteractOutcome::Failed(reason.into()),
+		}
+	}
+}
+
+/// Stages of the interaction pipeline, run in order in `Update`. Events sent
+/// in `Send` are handled and reported within the same frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, SystemSet)]
+pub enum InteractSet {
+	Send,
+	Handle,
+	Report,
+}
+
+/// Interactible under the mouse cursor, if any.
+#[derive(Clone, Copy, Debug, Default, Resource)]
+pub struct Hovered(pub Option<Entity>);
+
+#[derive(Component)]
+struct Tooltip;
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.add_event::<InteractEvent>();
+	app.add_event::<InteractResult>();
+	app.init_resource::<Hovered>();
+	app.configure_sets(
+		Update,
+		(InteractSet::Send, InteractSet::Handle, InteractSet::Report).chain(),
+	);
+	app.add_systems(Startup, startup_gui);
+	app.add_systems(
+		Update,
+		(
+			(hover, highlight, update_tooltip, click, use_on_arrival)
+				.chain()
+				.after(move_cursor)
+				.in_set(InteractSet::Send),
+			report_results.in_set(InteractSet::Report),
+		),
+	);
+}
+
+fn startup_gui(mut cmd: Commands, assets: Res<AssetServer>) {
+	cmd.spawn((
+		Tooltip,
+		TextBundle {
+			text: Text::from_section(
+				"",
+				TextStyle {
+					font: assets.load("fonts/RedHatDisplay.ttf"),
+					font_size: 20.0,
+					color: Color::WHITE,
+				},
+			),
+			style: Style {
+				position_type: PositionType::Absolute,
+				padding: UiRect::all(Val::Px(4.0)),
+				..default()
+			},
+			background_color: BackgroundColor(Color::rgba(0.1, 0.1, 0.1, 0.8)),
+			visibility: Visibility::Hidden,
+			..default()
+		},
+	));
+}
+
+fn hover(world: &mut World) {
+	let mut cursor = world.query_filtered::<&Transform, With<Cursor>>();
+	let pos = cursor.single(world).translation.xy();
+	let target = find_interactible_entities(pos, tileRadius / 2.0, world)
+		.first()
+		.copied();
+
+	let mut hovered = world.resource_mut::<Hovered>();
+	if hovered.0 != target {
+		hovered.0 = target;
+	}
+}
+
+fn highlight(
+	hovered: Res<Hovered>,
+	mut sprites: Query<&mut IsoSprite, With<Interactible>>,
+	mut last: Local<Option<Entity>>,
+) {
+	if !hovered.is_changed() {
+		return;
+	}
+
+	// keep alpha, which may be faded
+	if let Some(mut sprite) = last.and_then(|ent| sprites.get_mut(ent).ok()) {
+		sprite.color = Color::WHITE.with_a(sprite.color.a());
+	}
+	if let Some(mut sprite) = hovered.0.and_then(|ent| sprites.get_mut(ent).ok()) {
+		sprite.color = highlightColor.with_a(sprite.color.a());
+	}
+	*last = hovered.0;
+}
+
+fn update_tooltip(
+	hovered: Res<Hovered>,
+	names: Query<&Name>,
+	window: Query<&Window, With<PrimaryWindow>>,
+	mut tooltip: Query<(&mut Text, &mut Style, &mut Visibility), With<Tooltip>>,
+) {
+	let (mut text, mut style, mut visibility) = tooltip.single_mut();
+	let cursor = window.get_single().ok().and_then(Window::cursor_position);
+	let (Some(target), Some(cursor)) = (hovered.0, cursor) else {
+		*visibility = Visibility::Hidden;
+		return;
+	};
+
+	*visibility = Visibility::Inherited;
+	let name = names.get(target).map_or("Something", |name| name.as_str());
+	if text.sections[0].value != name {
+		text.sections[0].value = name.into();
+	}
+	// offset so the mouse pointer doesn't cover it
+	let (left, top) = (Val::Px(cursor.x + 16.0), Val::Px(cursor.y + 16.0));
+	if style.left != left || style.top != top {
+		style.left = left;
+		style.top = top;
+	}
+}
+
+/// Returns the tile containing `pos` (in world space.)
+fn tile_at(pos: Vec2) -> TilePos {
+	let pos = (pos / tileRadius).round();
+	TilePos::of(pos.x as _, pos.y as _)
+}
+
+fn click(
+	mut cmd: Commands,
+	mut events: EventWriter<InteractEvent>,
+	actions: Res<Input<Action>>,
+	hovered: Res<Hovered>,
+	editor: Res<Editor>,
+	map: Option<Res<Map>>,
+	player: Query<(Entity, &Transform, &Stats), With<Player>>,
+	cursor: Query<&Transform, With<Cursor>>,
+	targets: Query<&GlobalTransform, With<Interactible>>,
+	doors: Query<(&Door, &PluckedTile)>,
+) {
+	let clicked = actions.just_pressed(Action::Click);
+	let kind = if clicked || actions.just_pressed(Action::Interact) {
+		InteractKind::Use
+	} else if actions.just_pressed(Action::Attack) {
+		InteractKind::Attack
+	} else if actions.just_pressed(Action::Examine) {
+		InteractKind::Examine
+	} else {
+		return;
+	};
+	// the editor has its own use for the mouse
+	if editor.enabled {
+		return;
+	}
+	let Some(map) = map else {
+		return;
+	};
+	let (player, transform, stats) = player.single();
+	let plyPos = transform.translation.xy();
+
+	let target = hovered.0.and_then(|ent| Some((ent, targets.get(ent).ok()?)));
+	let goal = match target {
+		Some((target, targetPos)) => {
+			let targetPos = targetPos.translation().xy();
+			// looking works from afar
+			if kind == InteractKind::Examine || targetPos.distance(plyPos) <= stats.reach() {
+				events.send(InteractEvent {
+					kind,
+					source: player,
+					target,
+				});
+				return;
+			}
+			tile_at(targetPos)
+		},
+		None => tile_at(cursor.single().translation.xy()),
+	};
+	// only clicks walk over, the interact key just uses what's in reach
+	if !clicked || (target.is_none() && !map[goal].is_walkable()) {
+		return;
+	}
+
+	let closed: HashSet<_> = doors
+		.iter()
+		.filter(|(door, _)| !door.is_open())
+		.map(|(_, &PluckedTile(pos))| pos)
+		.collect();
+	let Some(tiles) = map.find_path(tile_at(plyPos), goal, |pos| closed.contains(&pos)) else {
+		return;
+	};
+	cmd.entity(player).insert(WalkPath {
+		tiles: tiles.into(),
+		target: target.map(|(target, _)| target),
+	});
+}
+
+/// Uses the target of a [`WalkPath`] once the player is in reach.
+fn use_on_arrival(
+	mut cmd: Commands,
+	mut events: EventWriter<InteractEvent>,
+	player: Query<(Entity, &Transform, &Stats, &WalkPath), With<Player>>,
+	targets: Query<&GlobalTransform, With<Interactible>>,
+) {
+	let Ok((player, transform, stats, path)) = player.get_single() else {
+		return;
+	};
+	let Some(target) = path.target else {
+		return;
+	};
+
+	let plyPos = transform.translation.xy();
+	match targets.get(target) {
+		Ok(targetPos) if targetPos.translation().xy().distance(plyPos) <= stats.reach() => {
+			events.send(InteractEvent {
+				kind: InteractKind::Use,
+				source: player,
+				target,
+			});
+			cmd.entity(player).remove::<WalkPath>();
+		},
+		Ok(_) if !path.tiles.is_empty() => {},
+		// ran out of path without getting in reach, or the target is gone
+		_ => {
+			cmd.entity(player).remove::<WalkPath>();
+		},
+	}
+}
+
+/// Gives feedback on interactions: failures, and events no handler answered.
+/// Examining something nobody has more to say about just names it.
+fn report_results(
+	mut events: EventReader<InteractEvent>,
+	mut results: EventReader<InteractResult>,
+	names: Query<&Name>,
+) {
+	let mut handled = vec![];
+	for result in &mut results {
+		if let InteractOutcome::Failed(reason) = &result.outcome {
+			FeedEntry::new(reason.clone())
+				.category(FeedCategory::Interaction)
+				.severity(Severity::Warning)
+				.submit();
+		}
+		handled.push(result.event);
+	}
+
+	for ev in &mut events {
+		// each result answers one event, so the same event sent twice needs two
+		if let Some(i) = handled.iter().position(|handled| handled == ev) {
+			handled.swap_remove(i);
+			continue;
+		}
+		let text = match (ev.kind, names.get(ev.target)) {
+			(InteractKind::Examine, Ok(name)) => format!("You see a {name}."),
+			_ => "Nothing happens.".into(),
+		};
+		FeedEntry::new(text).category(FeedCategory::Interaction).submit();
+	}
+}
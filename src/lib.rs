@@ -0,0 +1,338 @@
+#![allow(unused, non_snake_case, non_upper_case_globals)]
+
+pub mod anim;
+pub mod audio;
+pub mod camera;
+pub mod editor;
+pub mod entities;
+pub mod feed;
+pub mod input;
+pub mod interact;
+pub mod light;
+pub mod map;
+pub mod menu;
+pub mod particles;
+pub mod rng;
+pub mod save;
+pub mod settings;
+
+use std::collections::HashSet;
+use std::ops::Deref;
+
+pub use anyhow::Result as AResult;
+use bevy::ecs::system::CommandQueue;
+use bevy::hierarchy::despawn_with_children_recursive;
+use bevy::input::InputPlugin;
+use bevy::math::{ivec2, uvec2, vec2, vec3, Affine3A, Vec3Swizzles};
+use bevy::prelude::*;
+use bevy::render::extract_component::ExtractComponent;
+use bevy::render::primitives::Aabb;
+use bevy::render::render_resource::{Extent3d, FilterMode, TextureDimension, TextureFormat};
+use bevy::render::{Extract, RenderApp, RenderSet};
+use bevy::sprite::{ExtractedSprite, ExtractedSprites, SpriteSystem};
+use bevy::time::TimePlugin;
+use bevy::window::ExitCondition;
+use bevy_rapier2d::prelude::{RapierConfiguration, RapierContext, RapierPhysicsPlugin};
+use bevy_rapier2d::render::{DebugRenderContext, RapierDebugRenderPlugin};
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+
+use self::entities::player::{depthRange, Player};
+use self::map::TilePos;
+
+#[linkme::distributed_slice]
+pub static setupApp: [fn(&mut App)] = [..];
+
+#[linkme::distributed_slice]
+pub static setupMap: [fn(&mut map::MutMap, &mut Commands, &AssetServer)] = [..];
+
+/// Render layers of [`IsoSprite`]s, back to front. Each layer owns a disjoint
+/// slice of the camera's depth range, so a sprite never sorts in front of a
+/// sprite of a later layer; within a layer, sprites further down the screen
+/// are drawn in front.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[repr(u8)]
+pub enum RenderLayer {
+	/// Floor tiles.
+	Floor,
+	/// Flat things lying on the floor, e.g. traps.
+	FloorDecal,
+	/// Walls, landmarks, doors and actors: everything that stands upright.
+	#[default]
+	Object,
+	/// Things drawn above every object, e.g. explosions.
+	Overhead,
+	/// World-space interface elements that must never be covered, e.g. the
+	/// tile cursor.
+	WorldUi,
+}
+
+impl RenderLayer {
+	const count: u8 = Self::WorldUi as u8 + 1;
+
+	/// Width of the slice of depth values owned by each layer.
+	pub const depthSpan: f32 = depthRange / Self::count as f32;
+
+	/// Returns the depth of a sprite on this layer whose sort point is `pos`
+	/// (in world space.)
+	pub fn depth(self, pos: Vec2) -> f32 {
+		let isoY = (pos.x - pos.y) / 2.0;
+		let base = self as u8 as f32 * Self::depthSpan;
+		base + Self::depthSpan / 2.0 - isoY
+	}
+}
+
+#[derive(Clone, Debug, Component)]
+pub struct IsoSprite {
+	pub rect: Rect,
+	pub flip: bool,
+	pub layer: RenderLayer,
+	/// Size of the sprite's footprint in tiles, extending east (`x`) and south
+	/// (`y`) of its position. Sprites sort by the middle of their footprint,
+	/// so that large sprites cover what stands behind them on any side.
+	pub footprint: UVec2,
+	/// Tint multiplied with the texture, e.g. to fade out sprites.
+	pub color: Color,
+}
+
+impl IsoSprite {
+	/// Returns the world-space point this sprite is depth sorted by, given its
+	/// position.
+	pub fn sort_point(&self, pos: Vec2) -> Vec2 {
+		// sorting by the front-most tile instead would tie with, or even cover,
+		// actors standing beside the footprint's far end
+		let middle = (self.footprint.max(UVec2::ONE) - UVec2::ONE).as_vec2() / 2.0;
+		pos + middle * crate::map::tileRadius
+	}
+
+	/// Returns the area this sprite covers in isometric screen space, given
+	/// its position.
+	pub fn screen_rect(&self, pos: Vec2) -> Rect {
+		Rect::from_center_size(world_to_iso(pos).xy(), self.rect.size())
+	}
+}
+
+impl Default for IsoSprite {
+	fn default() -> Self {
+		Self {
+			rect: default(),
+			flip: false,
+			layer: default(),
+			footprint: UVec2::ONE,
+			color: Color::WHITE,
+		}
+	}
+}
+
+#[derive(Debug, Default, Bundle)]
+pub struct IsoSpriteBundle {
+	pub texture: Handle<Image>,
+
+	pub sprite: IsoSprite,
+
+	pub transform: TransformBundle,
+
+	pub visibility: VisibilityBundle,
+}
+
+/// Converts a world-space position into isometric screen space. The returned
+/// depth is that of an object standing at `pos`, see [`RenderLayer`].
+pub fn world_to_iso(pos: Vec2) -> Vec3 {
+	let (ix, iy) = pos.into();
+	let iso = vec2(ix + iy, (ix - iy) / 2.0);
+	(iso, RenderLayer::Object.depth(pos)).into()
+}
+
+pub fn iso_to_world(pos: Vec2) -> Vec2 {
+	let (x, y) = pos.into();
+	vec2(x / 2.0 + y, x / 2.0 - y)
+}
+
+pub fn isosprite_extract(
+	mut query: Extract<Query<(Entity, &GlobalTransform, &Handle<Image>, &IsoSprite)>>,
+	lightMap: Extract<Res<light::LightMap>>,
+	mut extractedSprites: ResMut<ExtractedSprites>,
+	time: Res<Time>,
+	mut last: Local<f32>,
+) {
+	for (entity, transform, texture, sprite) in query.iter() {
+		let mut affine = transform.affine();
+		let pos = affine.translation.xy();
+		let mut isoPos = world_to_iso(pos);
+		// world-space z only breaks ties within a layer, and must stay well below
+		// the distance between adjacent tiles
+		isoPos.z = sprite.layer.depth(sprite.sort_point(pos)) + affine.translation.z;
+		affine.translation = isoPos.into();
+		extractedSprites.sprites.push(ExtractedSprite {
+			entity,
+			transform: affine.into(),
+			color: match sprite.layer {
+				RenderLayer::WorldUi => sprite.color,
+				_ => lightMap.tint(sprite.color, pos),
+			},
+			rect: Some(sprite.rect),
+			custom_size: None,
+			image_handle_id: texture.id(),
+			flip_x: sprite.flip,
+			flip_y: false,
+			anchor: Vec2::ZERO, // center
+		});
+	}
+
+	let now = time.elapsed_seconds();
+	#[cfg(none)]
+	if now - *last > 1.0 {
+		*last = now;
+		eprintln!("{} sprites", extractedSprites.sprites.len());
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Component)]
+pub struct Interactible;
+
+/// Returns the interactible entities whose colliders come within `radius` of
+/// `pos`, nearest first.
+pub fn find_interactible_entities(pos: Vec2, radius: f32, world: &World) -> Vec<Entity> {
+	assert!(radius > 0.0);
+
+	let pos = Vec3::from((pos, 0.0));
+	let aabb = Aabb::from_min_max(
+		pos + vec3(-radius, -radius, 0.0),
+		pos + vec3(radius, radius, 0.0),
+	);
+
+	let mut ents = vec![];
+	let rapier: &RapierContext = world.resource();
+	rapier.colliders_with_aabb_intersecting_aabb(aabb, |ent| {
+		if world.get::<Interactible>(ent).is_some() {
+			ents.push(ent);
+		}
+		true
+	});
+
+	let distance = |ent: Entity| {
+		world
+			.get::<GlobalTransform>(ent)
+			.map_or(f32::INFINITY, |transform| transform.translation().xy().distance(pos.xy()))
+	};
+	ents.sort_by(|&a, &b| distance(a).total_cmp(&distance(b)));
+	ents
+}
+
+/// The game itself: physics and everything registered in [`setupApp`]. Needs
+/// either `DefaultPlugins`, or `MinimalPlugins` and [`HeadlessPlugin`] to run
+/// without a window or GPU.
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+	fn build(&self, app: &mut App) {
+		let mut rapierConfig = RapierConfiguration::default();
+		rapierConfig.gravity = Vec2::ZERO;
+		app.insert_resource(rapierConfig);
+		app.add_plugins(RapierPhysicsPlugin::<()>::pixels_per_meter(
+			crate::map::tileDiameter,
+		));
+
+		for func in setupApp {
+			func(app);
+		}
+
+		let Ok(renderApp) = app.get_sub_app_mut(RenderApp) else {
+			return;
+		};
+		renderApp.add_systems(
+			ExtractSchedule,
+			isosprite_extract.after(SpriteSystem::ExtractSprites),
+		);
+		#[cfg(debug_assertions)]
+		{
+			app.add_plugins(RapierDebugRenderPlugin::default().disabled());
+			app.add_systems(Update, toggle_rapier_debug);
+		}
+	}
+}
+
+/// What the game needs from `DefaultPlugins` besides rendering, for running
+/// under `MinimalPlugins` in tests. There is no window, so the cursor stays
+/// wherever it's put, and the player's configuration and saves are neither
+/// read nor written. As `app.run()` isn't called, call `app.finish()` before the first
+/// update.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_plugins((
+			TransformPlugin,
+			HierarchyPlugin,
+			InputPlugin,
+			AssetPlugin::default(),
+			WindowPlugin {
+				primary_window: None,
+				exit_condition: ExitCondition::DontExit,
+				close_when_requested: false,
+			},
+			// loads textures, so floor meshes get built
+			ImagePlugin::default(),
+		));
+		app.add_asset::<TextureAtlas>();
+		app.add_asset::<Mesh>();
+		app.add_asset::<ColorMaterial>();
+		app.insert_resource(settings::UserConfig::none());
+	}
+}
+
+/// Marks the entities of the current level, so they can be despawned when
+/// another one is loaded.
+#[derive(Clone, Copy, Debug, Default, Component)]
+pub struct LevelEntity;
+
+/// Which level is loaded, and the seed it was generated from.
+#[derive(Clone, Copy, Debug, Resource, Deserialize, Serialize)]
+pub struct LevelInfo {
+	pub level: usize,
+	pub seed: u64,
+}
+
+/// Replaces the current level, if any, with `map`.
+pub fn load_level(world: &mut World, info: LevelInfo, mut map: map::MutMap) {
+	// tiles painted in the editor aren't tagged, but belong to the level too
+	let mut level = world.query_filtered::<
+		Entity,
+		Or<(With<LevelEntity>, With<map::MapTile>, With<map::PluckedTile>)>,
+	>();
+	for ent in level.iter(world).collect::<Vec<_>>() {
+		despawn_with_children_recursive(world, ent);
+	}
+
+	// whatever `setupMap` spawns belongs to the level
+	let before: HashSet<Entity> = world.iter_entities().map(|ent| ent.id()).collect();
+	let assets = world.resource::<AssetServer>().clone();
+	let mut queue = CommandQueue::default();
+	{
+		let mut cmd = Commands::new(&mut queue, world);
+		for func in setupMap {
+			func(&mut map, &mut cmd, &assets);
+		}
+		let map = map.into_entities(&mut cmd, &assets);
+		cmd.insert_resource(map);
+		cmd.insert_resource(info);
+		cmd.insert_resource(rng::GameRng::new(info.seed));
+	}
+	queue.apply(world);
+
+	let spawned: Vec<_> = world
+		.iter_entities()
+		.filter(|ent| !before.contains(&ent.id()) && !ent.contains::<Parent>())
+		.map(|ent| ent.id())
+		.collect();
+	for ent in spawned {
+		world.entity_mut(ent).insert(LevelEntity);
+	}
+}
+
+fn toggle_rapier_debug(actions: Res<Input<input::Action>>, mut ctx: ResMut<DebugRenderContext>) {
+	if actions.just_pressed(input::Action::ToggleDebug) {
+		ctx.enabled = !ctx.enabled;
+	}
+}
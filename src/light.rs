@@ -0,0 +1,247 @@
+//! Per-tile lighting. Light sources add up into the [`LightMap`], which tints
+//! sprites in `isosprite_extract` and floor meshes through their vertex
+//! colors. Walls block light, but are lit themselves.
+
+use std::collections::HashMap;
+
+use bevy::math::{vec3, Vec3Swizzles};
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+
+use crate::map::mesh::FloorMesh;
+use crate::map::{tileRadius, FloorType, Landmark, Map, TilePair, TilePos, TileRect, TileType};
+
+/// Emits light around an entity, or a map tile (see [`Light::of_tile`]).
+#[derive(Clone, Copy, Debug, Component)]
+pub struct Light {
+	pub color: Color,
+	/// Reach in tiles.
+	pub radius: f32,
+	/// Brightness at the source.
+	pub intensity: f32,
+	/// How much the brightness wavers over time, from 0 (steady) to 1.
+	pub flicker: f32,
+}
+
+impl Light {
+	pub const torch: Self = Self {
+		color: Color::rgb(1.0, 0.8, 0.55),
+		radius: 7.0,
+		intensity: 1.0,
+		flicker: 0.15,
+	};
+
+	/// Returns the light given off by a static map tile, if any.
+	pub fn of_tile(pair: &TilePair) -> Option<Self> {
+		let lava = |color| Self {
+			color,
+			radius: 2.5,
+			intensity: 0.8,
+			flicker: 0.2,
+		};
+		let portal = |color| Self {
+			color,
+			radius: 4.0,
+			intensity: 0.8,
+			flicker: 0.05,
+		};
+
+		use Landmark::*;
+		let light = match pair.foreground.ty {
+			TileType::Landmark { ty: ShrineFlame, .. } => Self {
+				color: Color::rgb(1.0, 0.6, 0.2),
+				radius: 5.0,
+				intensity: 1.0,
+				flicker: 0.3,
+			},
+			TileType::Landmark { ty: PortalRed | PortalDemon, .. } => portal(Color::rgb(1.0, 0.3, 0.2)),
+			TileType::Landmark { ty: PortalBlue, .. } => portal(Color::rgb(0.3, 0.5, 1.0)),
+			TileType::Landmark { ty: PortalGreen, .. } => portal(Color::rgb(0.3, 1.0, 0.4)),
+			TileType::Landmark {
+				ty:
+					PortalLight | PortalDark | PortalSkulls | PortalStar | PortalArch |
+					PortalWormhole | PortalBlank,
+				..
+			} => portal(Color::rgb(0.7, 0.5, 1.0)),
+			_ => match pair.background.ty {
+				TileType::Floor(FloorType::LavaRed) => lava(Color::rgb(1.0, 0.35, 0.1)),
+				TileType::Floor(FloorType::LavaBlue) => lava(Color::rgb(0.2, 0.4, 1.0)),
+				TileType::Floor(FloorType::LavaCyan) => lava(Color::rgb(0.2, 0.9, 1.0)),
+				_ => return None,
+			},
+		};
+		Some(light)
+	}
+
+	fn rgb(&self) -> Vec3 {
+		let [r, g, b, _] = self.color.as_rgba_f32();
+		vec3(r, g, b) * self.intensity
+	}
+
+	/// Returns the brightness multiplier at `time`. `seed` keeps neighboring
+	/// lights from flickering in lockstep.
+	fn flicker_at(&self, time: f32, seed: f32) -> f32 {
+		let noise = (time * 7.3 + seed).sin() * (time * 3.1 + seed * 1.7).sin();
+		1.0 - self.flicker * (0.5 + 0.5 * noise)
+	}
+
+	/// Returns the tiles reached by this light when placed at `origin`, and
+	/// how much of it reaches them.
+	fn illuminate(&self, map: &Map, origin: TilePos) -> Vec<(TilePos, f32)> {
+		TileRect::new(origin, origin)
+			.expand(self.radius.ceil() as i32)
+			.tiles()
+			.filter_map(|pos| {
+				let dist = (*pos - *origin).as_vec2().length();
+				if dist > self.radius || !line_of_sight(map, origin, pos) {
+					return None;
+				}
+				let falloff = 1.0 - dist / self.radius;
+				Some((pos, falloff * falloff))
+			})
+			.collect()
+	}
+}
+
+/// Whether light travels from `from` to `to`, i.e. no wall stands between
+/// them. The end tiles themselves don't count.
+fn line_of_sight(map: &Map, from: TilePos, to: TilePos) -> bool {
+	let delta = (*to - *from).as_vec2();
+	let steps = delta.x.abs().max(delta.y.abs()) as i32;
+	(1 .. steps).all(|step| {
+		let pos = from.as_vec2() + delta * (step as f32 / steps as f32);
+		!map[TilePos::of(pos.x.round() as _, pos.y.round() as _)].is_wall()
+	})
+}
+
+/// Light level of every lit tile.
+#[derive(Clone, Debug, Resource)]
+pub struct LightMap {
+	/// Light level of tiles no light reaches.
+	pub ambient: Vec3,
+	levels: HashMap<TilePos, Vec3>,
+}
+
+impl Default for LightMap {
+	fn default() -> Self {
+		Self {
+			ambient: Vec3::splat(0.2),
+			levels: default(),
+		}
+	}
+}
+
+impl LightMap {
+	pub fn get(&self, pos: TilePos) -> Vec3 {
+		self.levels.get(&pos).copied().unwrap_or(self.ambient)
+	}
+
+	/// Returns the light level at a position in world space.
+	pub fn at(&self, pos: Vec2) -> Vec3 {
+		let pos = (pos / tileRadius).round();
+		self.get(TilePos::of(pos.x as _, pos.y as _))
+	}
+
+	/// Multiplies `color` with the light level at `pos` (in world space.)
+	pub fn tint(&self, color: Color, pos: Vec2) -> Color {
+		let light = self.at(pos).min(Vec3::ONE);
+		let [r, g, b, a] = color.as_rgba_f32();
+		Color::rgba(r * light.x, g * light.y, b * light.z, a)
+	}
+}
+
+/// Tiles lit by a static map light, computed once per map change.
+struct LitArea {
+	light: Light,
+	seed: f32,
+	tiles: Vec<(TilePos, f32)>,
+}
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.init_resource::<LightMap>();
+	app.add_systems(PostUpdate, (update_lights, light_floors).chain());
+}
+
+fn update_lights(
+	map: Option<Res<Map>>,
+	time: Res<Time>,
+	lights: Query<(Entity, &Light, &GlobalTransform)>,
+	mut lightMap: ResMut<LightMap>,
+	mut staticLights: Local<Vec<LitArea>>,
+	mut lastUpdate: Local<f32>,
+) {
+	// flicker doesn't need to be smoother than this, and floors get re-uploaded on every update
+	const updateInterval: f32 = 1.0 / 20.0;
+
+	let Some(map) = map else {
+		return;
+	};
+	let now = time.elapsed_seconds();
+	if !map.is_changed() && now - *lastUpdate < updateInterval {
+		return;
+	}
+	*lastUpdate = now;
+
+	if map.is_changed() {
+		*staticLights = map
+			.used_tiles()
+			.tiles()
+			.filter_map(|pos| {
+				let light = Light::of_tile(&map[pos])?;
+				Some(LitArea {
+					light,
+					seed: (pos.x as f32 * 12.9898 + pos.y as f32 * 78.233) % 100.0,
+					tiles: light.illuminate(&map, pos),
+				})
+			})
+			.collect();
+	}
+
+	let mut levels: HashMap<TilePos, Vec3> = HashMap::new();
+	let ambient = lightMap.ambient;
+	let mut add = |tiles: &[(TilePos, f32)], color: Vec3| {
+		for &(pos, amount) in tiles {
+			*levels.entry(pos).or_insert(ambient) += color * amount;
+		}
+	};
+
+	for area in staticLights.iter() {
+		let flicker = area.light.flicker_at(now, area.seed);
+		add(&area.tiles, area.light.rgb() * flicker);
+	}
+	for (ent, light, transform) in &lights {
+		let pos = (transform.translation().xy() / tileRadius).round();
+		let tiles = light.illuminate(&map, TilePos::of(pos.x as _, pos.y as _));
+		let flicker = light.flicker_at(now, ent.index() as f32);
+		add(&tiles, light.rgb() * flicker);
+	}
+
+	lightMap.levels = levels;
+}
+
+/// Writes light levels into the vertex colors of floor meshes.
+fn light_floors(
+	lightMap: Res<LightMap>,
+	floors: Query<(&FloorMesh, &Mesh2dHandle)>,
+	mut meshes: ResMut<Assets<Mesh>>,
+) {
+	if !lightMap.is_changed() {
+		return;
+	}
+
+	for (floor, Mesh2dHandle(handle)) in &floors {
+		let Some(mesh) = meshes.get_mut(handle) else {
+			continue;
+		};
+		let colors: Vec<[f32; 4]> = floor
+			.tiles
+			.iter()
+			.flat_map(|&pos| {
+				let light = lightMap.get(pos).min(Vec3::ONE);
+				[[light.x, light.y, light.z, 1.0]; 4]
+			})
+			.collect();
+		mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+	}
+}
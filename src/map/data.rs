@@ -4,12 +4,12 @@ use bevy::ecs::system::EntityCommands;
 use bevy::math::{ivec2, vec2};
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::tileRadius;
 use crate::map::Chunk;
 
-#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
 #[repr(u8)]
 pub enum Tileset {
 	#[default]
@@ -40,6 +40,33 @@ pub enum Tileset {
 }
 
 impl Tileset {
+	pub const all: &'static [Self] = &[
+		Self::BrickBlue,
+		Self::BrickCyan,
+		Self::BrickGreen,
+		Self::BrickPurple,
+		Self::BrickRed,
+		Self::BrickYellow,
+		Self::Catacomb,
+		Self::Cocutos,
+		Self::Crypt,
+		Self::Gallery,
+		Self::Gehena,
+		Self::Hive,
+		Self::Lair,
+		Self::Lapis,
+		Self::Moss,
+		Self::Mucus,
+		Self::Normal,
+		Self::PandemBlue,
+		Self::PandemGreen,
+		Self::PandemPurple,
+		Self::PandemRed,
+		Self::PandemYellow,
+		Self::Rock,
+		Self::Tunnel,
+	];
+
 	pub fn asset_path(self) -> &'static str {
 		match self {
 			Self::BrickBlue => "tiles/brick_blue.png",
@@ -112,7 +139,7 @@ impl From<(Collider, Vec2)> for PositionedCollider {
 	}
 }
 
-#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
 #[repr(u8)] // tileset atlases
 pub enum WallShape {
 	#[default]
@@ -139,6 +166,25 @@ pub enum WallShape {
 }
 
 impl WallShape {
+	pub const all: &'static [Self] = &[
+		Self::Pillar,
+		Self::North,
+		Self::East,
+		Self::South,
+		Self::West,
+		Self::Northeast,
+		Self::Northwest,
+		Self::Southeast,
+		Self::Southwest,
+		Self::Eastwest,
+		Self::Northsouth,
+		Self::Solid,
+		Self::SolidNorth,
+		Self::SolidEast,
+		Self::SolidSouth,
+		Self::SolidWest,
+	];
+
 	pub fn collider(self) -> PositionedCollider {
 		const fullRadius: f32 = tileRadius / 2.0;
 		const pillarRadius: f32 = fullRadius * 0.55;
@@ -212,7 +258,7 @@ impl WallShape {
 	}
 }
 
-#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
 #[repr(u8)] // misc atlas
 pub enum Landmark {
 	#[default]
@@ -276,7 +322,128 @@ pub enum Landmark {
 	ExplosionSmokeDark = 180,
 }
 
-#[derive(Clone, Copy, Debug, Default, Deserialize)]
+impl Landmark {
+	pub const all: &'static [Self] = &[
+		Self::Well,
+		Self::StatueDragon,
+		Self::StatueFace,
+		Self::StatueBronze,
+		Self::StairsMarbleTop,
+		Self::StairsMarbleBottom,
+		Self::StairsSandstoneTop,
+		Self::StairsSandstoneBottom,
+		Self::TrapArrow,
+		Self::TrapPentagram,
+		Self::TrapSkull,
+		Self::PortalLight,
+		Self::PortalDark,
+		Self::PortalRed,
+		Self::PortalBlue,
+		Self::PortalGreen,
+		Self::PortalSkulls,
+		Self::PortalStar,
+		Self::PortalArch,
+		Self::PortalDemon,
+		Self::PortalWormhole,
+		Self::PortalBlank,
+		Self::ShrinePalm,
+		Self::ShrineIdol,
+		Self::ShrineSkulls,
+		Self::ShrineGeode,
+		Self::ShrineFace,
+		Self::ShrineScroll,
+		Self::ShrineCross,
+		Self::ShrineFlame,
+		Self::ShrineLapis,
+		Self::ShrineSacrifice,
+		Self::ShrineDemon,
+		Self::ShrineUrn,
+		Self::ShrineChair,
+		Self::SpawnPlayer,
+		Self::SpawnWitch,
+		Self::SpawnWitchette,
+		Self::SpawnJester,
+		Self::SpawnRedDemon,
+		Self::SpawnYellowDemon,
+		Self::SpawnGreenDemon,
+		Self::SpawnBlueDemon,
+		Self::SpawnWingedDemon,
+		Self::Cursor,
+		Self::ExplosionRed,
+		Self::ExplosionBlue,
+		Self::ExplosionGreen,
+		Self::ExplosionSmokeLight,
+		Self::ExplosionSmokeDark,
+	];
+
+	/// Whether this landmark physically blocks movement through its tile.
+	pub fn is_obstacle(self) -> bool {
+		use Landmark::*;
+		matches!(
+			self,
+			Well | StatueDragon |
+				StatueFace |
+				StatueBronze |
+				ShrinePalm |
+				ShrineIdol |
+				ShrineSkulls |
+				ShrineGeode |
+				ShrineFace |
+				ShrineScroll |
+				ShrineCross |
+				ShrineFlame |
+				ShrineLapis |
+				ShrineSacrifice |
+				ShrineDemon |
+				ShrineUrn |
+				ShrineChair
+		)
+	}
+
+	/// Size in tiles of the floor area this landmark's sprite covers. Every
+	/// sprite of the misc atlas fits on a single tile so far; larger ones go
+	/// here.
+	pub fn footprint(self) -> UVec2 {
+		UVec2::ONE
+	}
+
+	/// Whether this landmark marks where the player or a monster spawns.
+	pub fn is_spawn(self) -> bool {
+		use Landmark::*;
+		matches!(
+			self,
+			SpawnPlayer |
+				SpawnWitch |
+				SpawnWitchette |
+				SpawnJester |
+				SpawnRedDemon |
+				SpawnYellowDemon |
+				SpawnGreenDemon |
+				SpawnBlueDemon |
+				SpawnWingedDemon
+		)
+	}
+
+	/// Returns the collider of [obstacle](Self::is_obstacle) landmarks.
+	pub fn collider(self) -> Option<PositionedCollider> {
+		const statueRadius: f32 = tileRadius / 2.0 * 0.6;
+		const shrineRadius: f32 = tileRadius / 2.0 * 0.9;
+		if !self.is_obstacle() {
+			return None;
+		}
+
+		use Landmark::*;
+		Some(match self {
+			StatueDragon | StatueFace | StatueBronze => {
+				Collider::cuboid(statueRadius, statueRadius).into()
+			},
+			Well => Collider::ball(shrineRadius).into(),
+			_ => Collider::cuboid(shrineRadius, shrineRadius).into(),
+		})
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
 #[repr(u8)] // misc atlas (except `::Tileset`)
 pub enum FloorType {
 	#[default]
@@ -289,7 +456,18 @@ pub enum FloorType {
 	Slab = 74,
 }
 
-#[derive(Clone, Copy, Debug, Default, Deserialize)]
+impl FloorType {
+	pub const all: &'static [Self] = &[
+		Self::Tileset,
+		Self::Black,
+		Self::LavaRed,
+		Self::LavaBlue,
+		Self::LavaCyan,
+		Self::Slab,
+	];
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum TileType {
 	#[default]
 	Empty,
@@ -310,7 +488,7 @@ pub enum TileType {
 	},
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct TilePos(IVec2);
 
 impl TilePos {
@@ -387,7 +565,7 @@ impl From<TilePos> for IVec2 {
 	}
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct ChunkPos(IVec2);
 
 impl ChunkPos {
@@ -466,6 +644,19 @@ impl Direction {
 			Direction::NorthWest => ivec2(-1, -1),
 		}
 	}
+
+	pub fn opposite(self) -> Self {
+		match self {
+			Direction::North => Direction::South,
+			Direction::NorthEast => Direction::SouthWest,
+			Direction::East => Direction::West,
+			Direction::SouthEast => Direction::NorthWest,
+			Direction::South => Direction::North,
+			Direction::SouthWest => Direction::NorthEast,
+			Direction::West => Direction::East,
+			Direction::NorthWest => Direction::SouthEast,
+		}
+	}
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -503,6 +694,18 @@ impl TileRect {
 		*self.max += by;
 	}
 
+	pub fn contains(&self, pos: TilePos) -> bool {
+		(self.min.x ..= self.max.x).contains(&pos.x) && (self.min.y ..= self.max.y).contains(&pos.y)
+	}
+
+	/// Returns this rect grown by `amount` tiles in every direction.
+	pub fn expand(&self, amount: i32) -> Self {
+		Self::new_presorted(
+			(*self.min - IVec2::splat(amount)).into(),
+			(*self.max + IVec2::splat(amount)).into(),
+		)
+	}
+
 	pub fn intersects(&self, other: &Self) -> bool {
 		self.min.x <= other.max.x &&
 			other.min.x <= self.max.x &&
@@ -0,0 +1,172 @@
+use super::*;
+
+/// Binary space partitioning: recursively splits the map rect and places a
+/// room in each leaf, then joins the rooms with carved corridors.
+pub struct BspGenerator;
+
+impl MapGenerator for BspGenerator {
+	fn generate(&self, params: &GenParams, seed: u64) -> AResult<MutMap> {
+		// smallest rect that still fits a room after the border is cut off
+		const minLeafSize: i32 = 20;
+
+		let mut rng = SmallRng::seed_from_u64(seed);
+
+		let mut roomRects = vec![];
+		let mut queue = VecDeque::new();
+		queue.push_back((0, false, params.rect()));
+		while let Some((depth, xAxis, rect)) = queue.pop_front() {
+			let size = rect.size();
+			let size = if xAxis { size.x } else { size.y };
+			// splitting turns one leaf into two
+			let leaves = roomRects.len() + queue.len() + 2;
+			if depth >= params.depth || leaves > params.rooms as usize || size < minLeafSize * 2 {
+				roomRects.push(rect);
+				continue;
+			}
+
+			let size = (size / 2 + rng.gen_range(-size / 4 .. size / 4))
+				.clamp(minLeafSize, size - minLeafSize);
+			let (l, r) = rect.split(xAxis, size);
+			queue.push_back((depth + 1, !xAxis, l));
+			queue.push_back((depth + 1, !xAxis, r));
+		}
+
+		let rng = MapRng::new(rng);
+		let mut res = MutMap::from_rng(&rng);
+
+		// generate rooms
+		let mut rooms = vec![];
+		let mut interiors = vec![];
+		for mut rect in roomRects.iter().copied() {
+			let borderSize = rng.as_mut().gen_range(3 .. 7);
+			*rect.min += IVec2::splat(borderSize);
+			*rect.max -= IVec2::splat(borderSize);
+
+			let mut trect = rect;
+			trect.translate(-*rect.min);
+			let (room, roomDoors) = reroll_room(&rng, trect, &params.theme)?;
+			res.copy_from(&room, rect.min);
+			interiors.push(rect);
+			let exits = roomDoors
+				.into_iter()
+				.map(|p| TilePos::from(*p + *rect.min))
+				.map(|door| Exit {
+					door,
+					dir: door_direction(&res, door),
+				})
+				.collect::<Vec<_>>();
+			rooms.push(exits);
+		}
+
+		carve_corridors(&mut res, &rooms, params);
+
+		place_walls(&mut res);
+		decorate(&mut res, &interiors, params);
+		populate(&mut res, &roomRects, params);
+
+		validate(&mut res, params)?;
+		Ok(res)
+	}
+}
+
+/// Returns the direction leading out of the room through `door`.
+fn door_direction(map: &Map, door: TilePos) -> Direction {
+	match map[door].foreground.ty {
+		TileType::DoorNS { .. } => {
+			if map[door.neighbor(Direction::East)].is_empty() {
+				Direction::East
+			} else {
+				Direction::West
+			}
+		},
+		TileType::DoorEW { .. } => {
+			if map[door.neighbor(Direction::North)].is_empty() {
+				Direction::North
+			} else {
+				Direction::South
+			}
+		},
+		_ => unreachable!(),
+	}
+}
+
+/// Generates a room, rerolling it a few times if it turns out unusable.
+fn reroll_room(
+	rng: &MapRng,
+	rect: TileRect,
+	theme: &Theme,
+) -> AResult<(MutMap, Vec<TilePos>)> {
+	const maxRerolls: usize = 8;
+
+	let mut res = generate_room(rng, rect, theme);
+	for _ in 0 .. maxRerolls {
+		if res.is_ok() {
+			break;
+		}
+		res = generate_room(rng, rect, theme);
+	}
+	res
+}
+
+fn generate_room(
+	rng: &MapRng,
+	rect: TileRect,
+	theme: &Theme,
+) -> AResult<(MutMap, Vec<TilePos>)> {
+	let mut res = MutMap::new(None);
+	let tileset = theme.room_tileset(rng);
+	res.fill(theme.room_floor(rng, tileset), rect.min, rect.max);
+	res.fill_border(
+		Tile {
+			ty: TileType::Wall(WallShape::Solid),
+			tileset,
+		},
+		rect.min,
+		rect.max,
+	);
+
+	// place a shrine
+	let shrinePos = {
+		let x = rng.as_mut().gen_range(rect.min.x + 1 .. rect.max.x);
+		let y = rng.as_mut().gen_range(rect.min.y + 1 .. rect.max.y);
+		TilePos::of(x, y)
+	};
+	if let Some(shrine) = theme.shrine(rng) {
+		res[shrinePos].foreground = shrine;
+	}
+
+	// place doors
+	let mut doors = vec![];
+	let numDoors = rng.as_mut().gen_range(1 ..= 4);
+	for _ in 0 .. numDoors {
+		'placing: for _ in 0 .. 1000 {
+			let pos = rect.tile_on_border(&mut *rng.as_mut());
+			for neighbor in pos.von_neumann_neighborhood() {
+				if res[neighbor].is_door() {
+					continue 'placing;
+				}
+			}
+
+			let door = if res[pos.neighbor(Direction::North)].is_wall() &&
+				res[pos.neighbor(data::Direction::South)].is_wall()
+			{
+				TileType::DoorNS { open: false }
+			} else if res[pos.neighbor(Direction::East)].is_wall() &&
+				res[pos.neighbor(data::Direction::West)].is_wall()
+			{
+				TileType::DoorEW { open: false }
+			} else {
+				// trying to place door on a corner?
+				continue 'placing;
+			};
+			res[pos].set(Tile { ty: door, tileset });
+			doors.push(pos);
+			break;
+		}
+	}
+	if doors.is_empty() {
+		bail!("could not place any doors in room {rect:?}");
+	}
+
+	Ok((res, doors))
+}
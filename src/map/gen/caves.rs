@@ -0,0 +1,130 @@
+use super::*;
+
+/// Cellular automaton caves: starts from random noise and repeatedly smooths
+/// it, keeping only the largest connected cavern.
+pub struct CaveGenerator;
+
+impl MapGenerator for CaveGenerator {
+	fn generate(&self, params: &GenParams, seed: u64) -> AResult<MutMap> {
+		const fillChance: f64 = 0.45;
+
+		let mut res = MutMap::new(Some(seed));
+		let rng = res.rng.clone();
+		let rect = params.rect();
+
+		let mut grid = CaveGrid::new(rect);
+		for pos in rect.tiles() {
+			let onBorder = pos.x == rect.min.x ||
+				pos.x == rect.max.x ||
+				pos.y == rect.min.y ||
+				pos.y == rect.max.y;
+			grid.set(pos, !onBorder && !rng.as_mut().gen_bool(fillChance));
+		}
+
+		for _ in 0 .. params.depth {
+			let mut next = grid.clone();
+			for pos in rect.tiles() {
+				let walls = pos.moore_neighborhood().filter(|&p| !grid.is_open(p)).count();
+				if walls > 4 {
+					next.set(pos, false);
+				} else if walls < 4 {
+					next.set(pos, true);
+				}
+			}
+			grid = next;
+		}
+
+		let cavern = grid.largest_region();
+		let floor = params.theme.corridor_floor(&rng);
+		for &pos in &cavern {
+			res[pos].set(floor);
+		}
+		place_walls(&mut res);
+
+		// scatter shrines throughout the cavern
+		for _ in 0 .. params.rooms {
+			let Some(&pos) = cavern.choose(&mut *rng.as_mut()) else {
+				break;
+			};
+			if let (true, Some(shrine)) = (res[pos].is_floor(), params.theme.shrine(&rng)) {
+				res[pos].foreground = shrine;
+			}
+		}
+
+		decorate(&mut res, &[rect], params);
+		populate(&mut res, &[rect], params);
+
+		validate(&mut res, params)?;
+		Ok(res)
+	}
+}
+
+#[derive(Clone)]
+struct CaveGrid {
+	rect: TileRect,
+	open: Vec<bool>,
+}
+
+impl CaveGrid {
+	fn new(rect: TileRect) -> Self {
+		let size = rect.size() + 1;
+		Self {
+			rect,
+			open: vec![false; (size.x * size.y) as usize],
+		}
+	}
+
+	fn index(&self, pos: TilePos) -> Option<usize> {
+		let rel = *pos - *self.rect.min;
+		let size = self.rect.size() + 1;
+		if rel.x < 0 || rel.y < 0 || rel.x >= size.x || rel.y >= size.y {
+			return None;
+		}
+		Some((rel.y * size.x + rel.x) as usize)
+	}
+
+	/// Tiles outside of the grid are considered walls.
+	fn is_open(&self, pos: TilePos) -> bool {
+		self.index(pos).map(|i| self.open[i]).unwrap_or(false)
+	}
+
+	fn set(&mut self, pos: TilePos, open: bool) {
+		if let Some(i) = self.index(pos) {
+			self.open[i] = open;
+		}
+	}
+
+	/// Returns all tiles of the largest 4-connected open region.
+	fn largest_region(&self) -> Vec<TilePos> {
+		let mut visited = vec![false; self.open.len()];
+		let mut best = vec![];
+		for start in self.rect.tiles() {
+			let i = self.index(start).unwrap();
+			if visited[i] || !self.open[i] {
+				continue;
+			}
+
+			let mut region = vec![];
+			let mut queue = VecDeque::new();
+			visited[i] = true;
+			queue.push_back(start);
+			while let Some(pos) = queue.pop_front() {
+				region.push(pos);
+				for neighbor in pos.von_neumann_neighborhood() {
+					let Some(j) = self.index(neighbor) else {
+						continue;
+					};
+					if !visited[j] && self.open[j] {
+						visited[j] = true;
+						queue.push_back(neighbor);
+					}
+				}
+			}
+
+			if region.len() > best.len() {
+				best = region;
+			}
+		}
+		best
+	}
+}
@@ -0,0 +1,128 @@
+use super::*;
+
+/// Returns all 4-connected regions of [walkable](TilePair::is_walkable)
+/// tiles, largest first.
+pub fn walkable_regions(map: &Map) -> Vec<Vec<TilePos>> {
+	let mut visited = HashSet::new();
+	let mut regions = vec![];
+	for start in map.used_tiles().tiles() {
+		if !map[start].is_walkable() || !visited.insert(start) {
+			continue;
+		}
+
+		let mut region = vec![];
+		let mut queue = VecDeque::from([start]);
+		while let Some(pos) = queue.pop_front() {
+			region.push(pos);
+			for neighbor in pos.von_neumann_neighborhood() {
+				if map[neighbor].is_walkable() && visited.insert(neighbor) {
+					queue.push_back(neighbor);
+				}
+			}
+		}
+		regions.push(region);
+	}
+	regions.sort_by_key(|region| std::cmp::Reverse(region.len()));
+	regions
+}
+
+/// Joins every walkable region to the largest one by carving floor paths,
+/// then walls the new paths in. Fails if any region remains isolated.
+pub fn ensure_connected(map: &mut MutMap, floor: Tile) -> AResult<()> {
+	let mut regions = walkable_regions(map);
+	if regions.len() <= 1 {
+		return Ok(());
+	}
+
+	let bounds = map.used_tiles().expand(1);
+	let mut main: HashSet<_> = regions.remove(0).into_iter().collect();
+	for region in regions {
+		let path = path_to(map, &region, &main, bounds).ok_or_else(|| {
+			anyhow!(
+				"no path from region at {:?} to the rest of the map",
+				region[0]
+			)
+		})?;
+		for &pos in &path {
+			if !map[pos].is_walkable() {
+				map[pos].set(floor);
+			}
+		}
+		main.extend(region);
+		main.extend(path);
+	}
+	place_walls(map);
+
+	let remaining = walkable_regions(map).len();
+	if remaining > 1 {
+		bail!("{remaining} walkable regions left after connecting");
+	}
+	Ok(())
+}
+
+/// Breadth-first search from `from` to any tile of `to`, able to cut through
+/// walls and empty space but not doors or obstacles. Returns the tiles
+/// between the two regions.
+fn path_to(
+	map: &Map,
+	from: &[TilePos],
+	to: &HashSet<TilePos>,
+	bounds: TileRect,
+) -> Option<Vec<TilePos>> {
+	let passable = |pos: TilePos| {
+		let pair = &map[pos];
+		bounds.contains(pos) &&
+			!pair.is_door() &&
+			!matches!(pair.foreground.ty, TileType::Landmark { ty, .. } if ty.is_obstacle())
+	};
+
+	let mut parents = HashMap::new();
+	let mut queue = VecDeque::new();
+	for &pos in from {
+		parents.insert(pos, None);
+		queue.push_back(pos);
+	}
+
+	while let Some(pos) = queue.pop_front() {
+		for neighbor in pos.von_neumann_neighborhood() {
+			if parents.contains_key(&neighbor) {
+				continue;
+			}
+			if to.contains(&neighbor) {
+				let mut path = vec![];
+				let mut cur = Some(pos);
+				while let Some(p) = cur {
+					path.push(p);
+					cur = parents[&p];
+				}
+				return Some(path);
+			}
+			if passable(neighbor) {
+				parents.insert(neighbor, Some(pos));
+				queue.push_back(neighbor);
+			}
+		}
+	}
+	None
+}
+
+/// Checks that the map has at least one player spawnpoint and that the whole
+/// map is walkable from it, repairing disconnected regions where possible.
+pub fn validate(map: &mut MutMap, params: &GenParams) -> AResult<()> {
+	let floor = params.theme.corridor_floor(&map.rng);
+	ensure_connected(map, floor)?;
+
+	let hasSpawn = map.used_tiles().tiles().any(|pos| {
+		matches!(
+			map[pos].foreground.ty,
+			TileType::Landmark {
+				ty: Landmark::SpawnPlayer,
+				..
+			}
+		)
+	});
+	if !hasSpawn {
+		bail!("no player spawnpoints were placed");
+	}
+	Ok(())
+}
@@ -0,0 +1,264 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use super::*;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CorridorParams {
+	/// Width of corridors in tiles.
+	pub width: u32,
+	/// Number of random waypoints each corridor detours through.
+	pub bends: u32,
+	/// Extra cost of turning a corner while pathing; higher values produce
+	/// straighter corridors.
+	pub turnCost: u32,
+	/// Chance of adding each short non-tree connection, creating loops.
+	pub loopChance: f64,
+}
+
+impl Default for CorridorParams {
+	fn default() -> Self {
+		Self {
+			width: 1,
+			bends: 0,
+			turnCost: 6,
+			loopChance: 0.15,
+		}
+	}
+}
+
+/// A door leading out of a room, and the direction it faces.
+#[derive(Clone, Copy, Debug)]
+pub struct Exit {
+	pub door: TilePos,
+	pub dir: Direction,
+}
+
+impl Exit {
+	/// The tile just outside of the door, where corridors attach.
+	pub fn outside(&self) -> TilePos {
+		self.door.neighbor(self.dir)
+	}
+}
+
+/// Connects rooms (given as their exits) with corridors along a minimum
+/// spanning tree, plus a few extra edges to form loops. Exits that end up
+/// without a corridor are walled over.
+pub fn carve_corridors(map: &mut MutMap, rooms: &[Vec<Exit>], params: &GenParams) {
+	let rng = map.rng.clone();
+	let corridorParams = &params.corridors;
+	let bounds = map.used_tiles().expand(corridorParams.width as i32 + 2);
+
+	let mut used: Vec<_> = rooms.iter().map(|exits| vec![false; exits.len()]).collect();
+
+	let mut corridorTiles = HashSet::new();
+	for (a, b) in corridor_graph(rooms, corridorParams.loopChance, &rng) {
+		let Some((ea, eb)) = closest_exits(&rooms[a], &rooms[b]) else {
+			continue;
+		};
+		let (from, to) = (rooms[a][ea], rooms[b][eb]);
+
+		let mut waypoints = vec![from.outside()];
+		let span = TileRect::new(from.outside(), to.outside());
+		for _ in 0 .. corridorParams.bends {
+			let waypoint = span.random_tile(&mut *rng.as_mut());
+			if map[waypoint].is_empty() {
+				waypoints.push(waypoint);
+			}
+		}
+		waypoints.push(to.outside());
+
+		let mut path = vec![];
+		for leg in waypoints.windows(2) {
+			let Some(legPath) = find_path(
+				map,
+				&corridorTiles,
+				leg[0],
+				leg[1],
+				bounds,
+				corridorParams.turnCost,
+			) else {
+				path.clear();
+				break;
+			};
+			path.extend(legPath);
+		}
+		if path.is_empty() {
+			continue;
+		}
+
+		// each corridor picks its own tileset from the theme
+		let floor = params.theme.corridor_floor(&rng);
+		// brushes wider than a tile spread out evenly around the path
+		let width = corridorParams.width.max(1) as i32;
+		let brush = TileRect::from_origin_size(
+			TilePos::of(-(width - 1) / 2, -(width - 1) / 2),
+			IVec2::splat(width - 1),
+		);
+		for pos in path {
+			for offset in brush.tiles() {
+				let tile = TilePos::from(*pos + *offset);
+				// the path itself is always carved, the brush only fills empty space
+				if tile == pos || map[tile].is_empty() {
+					map[tile].set(floor);
+					corridorTiles.insert(tile);
+				}
+			}
+		}
+		used[a][ea] = true;
+		used[b][eb] = true;
+	}
+
+	for (exits, used) in rooms.iter().zip(used) {
+		for (exit, _) in exits.iter().zip(used).filter(|(_, used)| !used) {
+			let tileset = map[exit.door].foreground.tileset;
+			map[exit.door].set(Tile {
+				ty: TileType::Wall(WallShape::Solid),
+				tileset,
+			});
+		}
+	}
+}
+
+/// Returns the edges of a minimum spanning tree over the rooms, with some
+/// of the remaining short edges mixed in.
+fn corridor_graph(rooms: &[Vec<Exit>], loopChance: f64, rng: &MapRng) -> Vec<(usize, usize)> {
+	let center = |exits: &[Exit]| -> Option<IVec2> {
+		let sum = exits.iter().map(|e| *e.door).reduce(|a, b| a + b)?;
+		Some(sum / exits.len() as i32)
+	};
+	let centers: Vec<_> = rooms.iter().map(|exits| center(exits.as_slice())).collect();
+
+	let mut edges = vec![];
+	for a in 0 .. rooms.len() {
+		for b in a + 1 .. rooms.len() {
+			let (Some(ca), Some(cb)) = (centers[a], centers[b]) else {
+				continue;
+			};
+			let dist = (ca - cb).abs();
+			edges.push((dist.x + dist.y, a, b));
+		}
+	}
+	edges.sort();
+
+	// Kruskal's algorithm
+	let mut parents: Vec<_> = (0 .. rooms.len()).collect();
+	fn find(parents: &mut [usize], i: usize) -> usize {
+		if parents[i] != i {
+			parents[i] = find(parents, parents[i]);
+		}
+		parents[i]
+	}
+
+	let mut tree = vec![];
+	let mut rest = vec![];
+	for (dist, a, b) in edges {
+		let (ra, rb) = (find(&mut parents, a), find(&mut parents, b));
+		if ra != rb {
+			parents[ra] = rb;
+			tree.push((dist, a, b));
+		} else {
+			rest.push((dist, a, b));
+		}
+	}
+
+	// only consider loops no longer than the longest tree edge, so they stay local
+	let maxLoop = tree.iter().map(|&(dist, ..)| dist).max().unwrap_or(0);
+	let loops = rest
+		.into_iter()
+		.filter(|&(dist, ..)| dist <= maxLoop)
+		.filter(|_| rng.as_mut().gen_bool(loopChance));
+
+	tree.into_iter()
+		.chain(loops)
+		.map(|(_, a, b)| (a, b))
+		.collect()
+}
+
+/// Returns indices of the exit pair of two rooms closest to each other.
+fn closest_exits(a: &[Exit], b: &[Exit]) -> Option<(usize, usize)> {
+	let mut best = None;
+	for (i, ea) in a.iter().enumerate() {
+		for (j, eb) in b.iter().enumerate() {
+			let dist = (*ea.outside() - *eb.outside()).abs();
+			let dist = dist.x + dist.y;
+			if best.map(|(d, ..)| dist < d).unwrap_or(true) {
+				best = Some((dist, i, j));
+			}
+		}
+	}
+	best.map(|(_, i, j)| (i, j))
+}
+
+/// A* from `from` to `to` through empty tiles and existing corridors,
+/// penalizing turns. Returns the path including both endpoints.
+fn find_path(
+	map: &Map,
+	corridors: &HashSet<TilePos>,
+	from: TilePos,
+	to: TilePos,
+	bounds: TileRect,
+	turnCost: u32,
+) -> Option<Vec<TilePos>> {
+	use Direction::*;
+	const dirs: [Direction; 4] = [North, East, South, West];
+
+	let passable = |pos: TilePos| {
+		pos == to || bounds.contains(pos) && (map[pos].is_empty() || corridors.contains(&pos))
+	};
+	if !passable(from) {
+		return None;
+	}
+	let heuristic = |pos: TilePos| {
+		let dist = (*pos - *to).abs();
+		(dist.x + dist.y) as u32
+	};
+
+	// states are a tile plus the direction it was entered from, so turns can be costed
+	let mut costs: HashMap<(TilePos, Option<usize>), u32> = HashMap::new();
+	let mut parents = HashMap::new();
+	let mut queue = BinaryHeap::new();
+	let mut states = vec![(from, None)];
+	costs.insert((from, None), 0);
+	queue.push(Reverse((heuristic(from), 0)));
+
+	while let Some(Reverse((_, stateIdx))) = queue.pop() {
+		let state @ (pos, dir) = states[stateIdx];
+		if pos == to {
+			let mut path = vec![pos];
+			let mut cur = state;
+			while let Some(&parent) = parents.get(&cur) {
+				path.push(parent.0);
+				cur = parent;
+			}
+			path.reverse();
+			return Some(path);
+		}
+
+		let cost = costs[&state];
+		for (i, &nextDir) in dirs.iter().enumerate() {
+			let next = pos.neighbor(nextDir);
+			if !passable(next) {
+				continue;
+			}
+
+			// existing corridors are cheaper, so corridors merge rather than run parallel
+			let mut stepCost = if corridors.contains(&next) { 1 } else { 2 };
+			if dir.is_some_and(|dir| dir != i) {
+				stepCost += turnCost;
+			}
+			let nextState = (next, Some(i));
+			let nextCost = cost + stepCost;
+			if costs.get(&nextState).is_some_and(|&c| c <= nextCost) {
+				continue;
+			}
+
+			costs.insert(nextState, nextCost);
+			parents.insert(nextState, state);
+			states.push(nextState);
+			queue.push(Reverse((nextCost + heuristic(next), states.len() - 1)));
+		}
+	}
+	None
+}
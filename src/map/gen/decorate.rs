@@ -0,0 +1,82 @@
+use super::*;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct DecorationParams {
+	/// Decorative landmarks placed per 100 floor tiles of a room.
+	pub landmarkDensity: f32,
+	/// Floor patches placed per 100 floor tiles of a room.
+	pub patchDensity: f32,
+	/// Largest radius of a floor patch, in tiles.
+	pub patchRadius: i32,
+}
+
+impl Default for DecorationParams {
+	fn default() -> Self {
+		Self {
+			landmarkDensity: 1.0,
+			patchDensity: 0.5,
+			patchRadius: 3,
+		}
+	}
+}
+
+/// Scatters the theme's decorative landmarks and floor patches over `rooms`,
+/// scaled by how much floor each room has. Landmarks are only placed where
+/// they cannot block a door or cut off a path.
+pub fn decorate(map: &mut MutMap, rooms: &[TileRect], params: &GenParams) {
+	let rng = map.rng.clone();
+	let decorations = &params.decorations;
+	let theme = &params.theme;
+
+	for room in rooms {
+		let floorTiles = room.tiles().filter(|&pos| map[pos].is_floor()).count() as f32;
+		let count = |density: f32| (floorTiles * density / 100.0).round() as u32;
+
+		for _ in 0 .. count(decorations.patchDensity) {
+			let Some(floor) = theme.floor_patch(&rng) else {
+				break;
+			};
+			let center = room.random_tile(&mut *rng.as_mut());
+			let radius = rng.as_mut().gen_range(1 ..= decorations.patchRadius.max(1));
+			let patch = TileRect::new(center, center).expand(radius);
+			for pos in patch.tiles() {
+				// ragged edges look less like stamped circles
+				let dist = (*pos - *center).as_vec2().length();
+				let inside = dist + rng.as_mut().gen_range(-0.5 .. 0.5) <= radius as f32;
+				let pair = &mut map[pos];
+				let isFloor = matches!(pair.background.ty, TileType::Floor(_));
+				if inside && room.contains(pos) && isFloor && !pair.is_wall() && !pair.is_door() {
+					pair.background.ty = TileType::Floor(floor);
+				}
+			}
+		}
+
+		let mut placed = 0;
+		let wanted = count(decorations.landmarkDensity);
+		for _ in 0 .. wanted * 20 {
+			if placed >= wanted {
+				break;
+			}
+
+			let pos = room.random_tile(&mut *rng.as_mut());
+			if !can_obstruct(map, pos) {
+				continue;
+			}
+			let Some(landmark) = theme.landmark(&rng) else {
+				break;
+			};
+			map[pos].foreground = landmark;
+			placed += 1;
+		}
+	}
+}
+
+/// Whether an obstacle can be put on `pos` without blocking anything. Only
+/// open floor fully surrounded by walkable tiles qualifies: the ring of
+/// neighbors stays connected, so every path through `pos` can go around it.
+fn can_obstruct(map: &Map, pos: TilePos) -> bool {
+	map[pos].is_floor() &&
+		pos.moore_neighborhood()
+			.all(|neighbor| map[neighbor].is_walkable() && !map[neighbor].is_door())
+}
@@ -0,0 +1,74 @@
+use super::*;
+
+/// Drunkard's walk: random walkers carve winding tunnels until enough of the
+/// map is open, each leaving a small chamber with a shrine where it stops.
+pub struct DrunkardGenerator;
+
+impl MapGenerator for DrunkardGenerator {
+	fn generate(&self, params: &GenParams, seed: u64) -> AResult<MutMap> {
+		// fraction of the map that should end up as floor
+		const coverage: f32 = 0.3;
+
+		let mut res = MutMap::new(Some(seed));
+		let rng = res.rng.clone();
+		let rect = params.rect();
+		// keep a margin so the surrounding walls stay inside the map rect
+		let bounds = TileRect::new_presorted(
+			(*rect.min + IVec2::splat(2)).into(),
+			(*rect.max - IVec2::splat(2)).into(),
+		);
+		let area = (bounds.size().x * bounds.size().y).max(1) as f32;
+		let target = (area * coverage) as usize;
+		let walkers = params.rooms.max(1) as usize;
+		let steps = target / walkers + 1;
+
+		let floor = params.theme.corridor_floor(&rng);
+
+		// kept in carving order as well, so picking a random tile is deterministic
+		let mut carved = HashSet::new();
+		let mut carvedOrder = vec![];
+
+		let mut chambers = vec![];
+		let mut pos = bounds.center();
+		while carvedOrder.len() < target && chambers.len() < walkers * 4 {
+			for _ in 0 .. steps {
+				if carved.insert(pos) {
+					carvedOrder.push(pos);
+					res[pos].set(floor);
+				}
+
+				use Direction::*;
+				let dir = *[North, East, South, West].choose(&mut *rng.as_mut()).unwrap();
+				let next = pos.neighbor(dir);
+				if bounds.contains(next) {
+					pos = next;
+				}
+			}
+
+			let chamber = TileRect::new(
+				(*pos - IVec2::ONE).max(*bounds.min).into(),
+				(*pos + IVec2::ONE).min(*bounds.max).into(),
+			);
+			for tile in chamber.tiles() {
+				if carved.insert(tile) {
+					carvedOrder.push(tile);
+					res[tile].set(floor);
+				}
+			}
+			if let Some(shrine) = params.theme.shrine(&rng) {
+				res[pos].foreground = shrine;
+			}
+			chambers.push(chamber);
+
+			// next walker starts somewhere already carved, so tunnels stay connected
+			pos = *carvedOrder.choose(&mut *rng.as_mut()).unwrap();
+		}
+
+		place_walls(&mut res);
+		decorate(&mut res, &[bounds], params);
+		populate(&mut res, &chambers, params);
+
+		validate(&mut res, params)?;
+		Ok(res)
+	}
+}
@@ -0,0 +1,282 @@
+pub mod bsp;
+pub mod caves;
+pub mod connectivity;
+pub mod corridors;
+pub mod decorate;
+pub mod drunkard;
+pub mod prefab_graph;
+pub mod theme;
+
+use std::collections::VecDeque;
+
+use anyhow::bail;
+use bevy::log::warn;
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+pub use self::bsp::BspGenerator;
+pub use self::caves::CaveGenerator;
+pub use self::connectivity::validate;
+pub use self::corridors::{carve_corridors, CorridorParams, Exit};
+pub use self::decorate::{decorate, DecorationParams};
+pub use self::drunkard::DrunkardGenerator;
+pub use self::prefab_graph::PrefabGraphGenerator;
+pub use self::theme::Theme;
+use super::data::Direction;
+use super::*;
+
+impl TileRect {
+	pub fn tiles(self) -> impl Iterator<Item = TilePos> {
+		(self.min.y ..= self.max.y)
+			.flat_map(move |y| (self.min.x ..= self.max.x).map(move |x| TilePos::of(x, y)))
+	}
+
+	pub fn split(&self, xAxis: bool, firstWidth: i32) -> (Self, Self) {
+		if xAxis {
+			let mid = self.min.x + firstWidth;
+			(
+				Self::new_presorted(self.min, TilePos::of(mid, self.max.y)),
+				Self::new_presorted(TilePos::of(mid, self.min.y), self.max),
+			)
+		} else {
+			let mid = self.min.y + firstWidth;
+			(
+				Self::new_presorted(self.min, TilePos::of(self.max.x, mid)),
+				Self::new_presorted(TilePos::of(self.min.x, mid), self.max),
+			)
+		}
+	}
+
+	pub fn tile_on_border(&self, rng: &mut impl Rng) -> TilePos {
+		let dir: u32 = rng.gen_range(0 .. 4);
+		match dir {
+			0 | 1 => {
+				let x = rng.gen_range(self.min.x ..= self.max.x);
+				let y = if dir & 1 == 0 { self.min.y } else { self.max.y };
+				TilePos::of(x, y)
+			},
+			2 | 3 => {
+				let x = if dir & 1 == 0 { self.min.x } else { self.max.x };
+				let y = rng.gen_range(self.min.y ..= self.max.y);
+				TilePos::of(x, y)
+			},
+			_ => unreachable!(),
+		}
+	}
+
+	pub fn random_tile(&self, rng: &mut impl Rng) -> TilePos {
+		let x = rng.gen_range(self.min.x ..= self.max.x);
+		let y = rng.gen_range(self.min.y ..= self.max.y);
+		TilePos::of(x, y)
+	}
+}
+
+/// A map generation algorithm. Implementations should finish by calling
+/// [`validate`], so that any map they return is fully connected.
+pub trait MapGenerator {
+	fn generate(&self, params: &GenParams, seed: u64) -> AResult<MutMap>;
+}
+
+/// Runs `generator`, rerolling with a fresh seed whenever generation fails.
+pub fn generate_with_retries(
+	generator: &dyn MapGenerator,
+	params: &GenParams,
+	seed: u64,
+) -> AResult<MutMap> {
+	const maxAttempts: u64 = 16;
+
+	let mut lastError = None;
+	for attempt in 0 .. maxAttempts {
+		let seed = seed.wrapping_add(attempt);
+		match generator.generate(params, seed) {
+			Ok(map) => return Ok(map),
+			Err(err) => {
+				warn!("map generation with seed {seed} failed: {err:#}");
+				lastError = Some(err);
+			},
+		}
+	}
+	Err(lastError
+		.unwrap()
+		.context(format!("map generation failed after {maxAttempts} attempts")))
+}
+
+/// Parameters shared by all [`MapGenerator`]s. Each generator interprets
+/// `rooms` and `depth` in whatever way is most natural for its algorithm.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct GenParams {
+	/// Width and height of the generated map in tiles, centered on the origin.
+	pub size: (i32, i32),
+	/// Number of rooms for room-based generators; number of walkers, caverns,
+	/// etc. for others.
+	pub rooms: u32,
+	/// BSP split depth, cellular automaton iterations, and so on.
+	pub depth: u32,
+	/// How corridors between rooms are carved.
+	pub corridors: CorridorParams,
+	/// How densely rooms are decorated with landmarks and floor patches.
+	pub decorations: DecorationParams,
+	/// Number of player spawnpoints to place.
+	pub spawns: u32,
+	/// Number of monster spawnpoints to place.
+	pub mobs: u32,
+	/// Tilesets, floors and landmarks to build the map from.
+	pub theme: Theme,
+}
+
+impl GenParams {
+	pub fn rect(&self) -> TileRect {
+		let (w, h) = self.size;
+		TileRect::from_origin_size(TilePos::of(-w / 2, -h / 2), ivec2(w, h))
+	}
+}
+
+impl Default for GenParams {
+	fn default() -> Self {
+		Self {
+			size: (120, 120),
+			rooms: 16,
+			depth: 4,
+			corridors: default(),
+			decorations: default(),
+			spawns: 5,
+			mobs: 0,
+			theme: default(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum GeneratorKind {
+	Bsp,
+	Caves,
+	Drunkard,
+	/// Asset paths of the prefabs to chain together.
+	PrefabGraph(Vec<String>),
+}
+
+impl GeneratorKind {
+	pub fn build(&self, assets: &AssetServer) -> AResult<Box<dyn MapGenerator>> {
+		Ok(match self {
+			Self::Bsp => Box::new(BspGenerator),
+			Self::Caves => Box::new(CaveGenerator),
+			Self::Drunkard => Box::new(DrunkardGenerator),
+			Self::PrefabGraph(paths) => {
+				let prefabs = paths
+					.iter()
+					.map(|path| Prefab::load_blocking(assets, path))
+					.collect::<AResult<_>>()?;
+				Box::new(PrefabGraphGenerator { prefabs })
+			},
+		})
+	}
+}
+
+/// Entry of `levels.ron`, describing how to generate a single dungeon level.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LevelDef {
+	pub generator: GeneratorKind,
+	/// Asset path of a theme file, replacing `params.theme`.
+	#[serde(default)]
+	pub theme: Option<String>,
+	#[serde(default)]
+	pub params: GenParams,
+}
+
+impl LevelDef {
+	/// Loads the entry of `levels.ron` for the given dungeon level. Levels
+	/// past the end of the list reuse the last definition.
+	pub fn load_blocking(assets: &AssetServer, level: usize) -> AResult<Self> {
+		let levels: Vec<Self> = load_ron_blocking(assets, "levels.ron")?;
+		levels
+			.get(level)
+			.or(levels.last())
+			.cloned()
+			.ok_or_else(|| anyhow!("levels.ron defines no levels"))
+	}
+
+	/// Loads the level's theme file, if it names one.
+	pub fn load_theme(&self, assets: &AssetServer) -> AResult<Theme> {
+		match &self.theme {
+			Some(path) => Theme::load_blocking(assets, path),
+			None => Ok(self.params.theme.clone()),
+		}
+	}
+}
+
+/// Generates the given dungeon level as described by `levels.ron`.
+pub fn generate_level(assets: &AssetServer, level: usize, seed: u64) -> AResult<MutMap> {
+	let def = LevelDef::load_blocking(assets, level)?;
+	let mut params = def.params.clone();
+	params.theme = def.load_theme(assets)?;
+	generate_with_retries(&*def.generator.build(assets)?, &params, seed)
+}
+
+pub fn generate_map(seed: u64) -> AResult<MutMap> {
+	generate_with_retries(&BspGenerator, &GenParams::default(), seed)
+}
+
+/// Surrounds all floor tiles with solid walls, matching the floor's tileset.
+fn place_walls(map: &mut MutMap) {
+	let mapRect = map.used_tiles();
+	for pos in mapRect.tiles() {
+		if !map[pos].is_floor() {
+			continue;
+		}
+		let tileset = map[pos].background.tileset;
+		for neighbor in pos.moore_neighborhood() {
+			if map[neighbor].is_empty() {
+				map[neighbor].set(Tile {
+					ty: TileType::Wall(WallShape::Solid),
+					tileset,
+				});
+			}
+		}
+	}
+}
+
+/// Places player and monster spawnpoints on floor tiles within `regions`.
+fn populate(map: &mut MutMap, regions: &[TileRect], params: &GenParams) {
+	let rng = map.rng.clone();
+	let player = Tile {
+		ty: TileType::Landmark {
+			ty: Landmark::SpawnPlayer,
+			flip: false,
+		},
+		..default()
+	};
+	place_landmarks(map, regions, params.spawns, || Some(player));
+	place_landmarks(map, regions, params.mobs, || params.theme.mob(&rng));
+}
+
+/// Places up to `count` landmarks produced by `landmark` on random floor
+/// tiles within `regions`.
+fn place_landmarks(
+	map: &mut MutMap,
+	regions: &[TileRect],
+	count: u32,
+	mut landmark: impl FnMut() -> Option<Tile>,
+) {
+	let rng = map.rng.clone();
+	let mut placed = 0;
+	for _ in 0 .. 1000 {
+		if placed >= count {
+			break;
+		}
+
+		let Some(region) = regions.choose(&mut *rng.as_mut()) else {
+			return;
+		};
+		let pos = region.random_tile(&mut *rng.as_mut());
+		if !map[pos].is_floor() {
+			continue;
+		}
+		let Some(tile) = landmark() else {
+			return;
+		};
+		map[pos].foreground = tile;
+		placed += 1;
+	}
+}
@@ -0,0 +1,117 @@
+use rand::seq::IteratorRandom;
+
+use super::*;
+
+/// Grows a tree of prefab rooms: each new prefab is attached by one of its
+/// doors to an unused door of an already placed prefab, joined by a short
+/// corridor. Doors left unused at the end are walled over.
+pub struct PrefabGraphGenerator {
+	pub prefabs: Vec<Prefab>,
+}
+
+impl MapGenerator for PrefabGraphGenerator {
+	fn generate(&self, params: &GenParams, seed: u64) -> AResult<MutMap> {
+		const maxCorridorLength: i32 = 6;
+
+		let mut res = MutMap::new(Some(seed));
+		let rng = res.rng.clone();
+		let bounds = params.rect();
+
+		let Some(first) = self.prefabs.choose(&mut *rng.as_mut()) else {
+			bail!("prefab graph generator has no prefabs");
+		};
+		let origin = TilePos::from(-first.size().as_ivec2() / 2);
+		first.copy_into(&mut res, origin);
+		let mut placed = vec![TileRect::from_origin_size(origin, first.size().as_ivec2() - 1)];
+		let mut sockets: Vec<_> = prefab_sockets(first)
+			.into_iter()
+			.map(|(pos, dir)| (TilePos::from(*pos + *origin), dir))
+			.collect();
+
+		'rooms: for _ in 1 .. params.rooms {
+			for _ in 0 .. 100 {
+				let Some(socketIdx) = (0 .. sockets.len()).choose(&mut *rng.as_mut()) else {
+					break 'rooms;
+				};
+				let (socket, dir) = sockets[socketIdx];
+
+				let prefab = self.prefabs.choose(&mut *rng.as_mut()).unwrap();
+				let candidates: Vec<_> = prefab_sockets(prefab)
+					.into_iter()
+					.filter(|&(_, other)| other == dir.opposite())
+					.collect();
+				let Some(&(target, _)) = candidates.choose(&mut *rng.as_mut()) else {
+					continue;
+				};
+
+				let length = rng.as_mut().gen_range(1 ..= maxCorridorLength);
+				let targetPos = TilePos::from(*socket + dir.delta() * (length + 1));
+				let origin = TilePos::from(*targetPos - *target);
+				let rect = TileRect::from_origin_size(origin, prefab.size().as_ivec2() - 1);
+				let overlaps = placed.iter().any(|other| other.expand(1).intersects(&rect));
+				if overlaps || rect.intersection(&bounds) != Some(rect) {
+					continue;
+				}
+
+				prefab.copy_into(&mut res, origin);
+				let corridor = params.theme.corridor_floor(&rng);
+				for i in 1 ..= length {
+					res[TilePos::from(*socket + dir.delta() * i)].set(corridor);
+				}
+				placed.push(rect);
+				sockets.swap_remove(socketIdx);
+				sockets.extend(
+					prefab_sockets(prefab)
+						.into_iter()
+						.filter(|&(pos, _)| pos != target)
+						.map(|(pos, dir)| (TilePos::from(*pos + *origin), dir)),
+				);
+				continue 'rooms;
+			}
+		}
+
+		// wall over doors that lead nowhere
+		for (pos, _) in sockets {
+			let tileset = res[pos].foreground.tileset;
+			res[pos].set(Tile {
+				ty: TileType::Wall(WallShape::Solid),
+				tileset,
+			});
+		}
+
+		place_walls(&mut res);
+		let hasSpawn = res.used_tiles().tiles().any(|pos| {
+			matches!(
+				res[pos].foreground.ty,
+				TileType::Landmark {
+					ty: Landmark::SpawnPlayer,
+					..
+				}
+			)
+		});
+		if hasSpawn {
+			let params = GenParams { spawns: 0, ..params.clone() };
+			populate(&mut res, &placed, &params);
+		} else {
+			populate(&mut res, &placed, params);
+		}
+
+		validate(&mut res, params)?;
+		Ok(res)
+	}
+}
+
+/// Returns the doors of `prefab` along with the direction they lead out of it.
+fn prefab_sockets(prefab: &Prefab) -> Vec<(TilePos, Direction)> {
+	use Direction::*;
+	prefab
+		.iter()
+		.filter(|(_, pair)| pair.is_door())
+		.filter_map(|(pos, _)| {
+			[North, East, South, West]
+				.into_iter()
+				.find(|&dir| prefab.get(pos.neighbor(dir)).is_none())
+				.map(|dir| (pos, dir))
+		})
+		.collect()
+}
@@ -0,0 +1,106 @@
+use super::*;
+
+/// A choice paired with its relative weight.
+pub type Weighted<T> = (T, f32);
+
+/// Level-wide look and feel of generated maps, loaded from `themes/*.ron`.
+/// Every list is a weighted palette that generation picks from.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+	/// Tilesets for room walls and tileset floors.
+	pub rooms: Vec<Weighted<Tileset>>,
+	/// Floor types for rooms; [`FloorType::Tileset`] uses the room's tileset.
+	pub floors: Vec<Weighted<FloorType>>,
+	/// Tilesets for corridors, tunnels and caves.
+	pub corridors: Vec<Weighted<Tileset>>,
+	/// Floor types for decorative floor patches.
+	pub floorPatches: Vec<Weighted<FloorType>>,
+	/// Shrines placed in rooms.
+	pub shrines: Vec<Weighted<Landmark>>,
+	/// Decorative landmarks such as statues and wells.
+	pub landmarks: Vec<Weighted<Landmark>>,
+	/// Monster spawnpoints.
+	pub mobs: Vec<Weighted<Landmark>>,
+	/// Asset path of the sound looped in the background.
+	pub ambient: Option<String>,
+}
+
+impl Theme {
+	pub fn load_blocking(assets: &AssetServer, path: &str) -> AResult<Self> {
+		load_ron_blocking(assets, path)
+	}
+
+	pub fn room_tileset(&self, rng: &MapRng) -> Tileset {
+		pick(rng, &self.rooms).unwrap_or(Tileset::Normal)
+	}
+
+	pub fn room_floor(&self, rng: &MapRng, tileset: Tileset) -> Tile {
+		Tile {
+			ty: TileType::Floor(pick(rng, &self.floors).unwrap_or_default()),
+			tileset,
+		}
+	}
+
+	pub fn corridor_floor(&self, rng: &MapRng) -> Tile {
+		Tile {
+			ty: TileType::Floor(FloorType::Tileset),
+			tileset: pick(rng, &self.corridors).unwrap_or(Tileset::Rock),
+		}
+	}
+
+	pub fn floor_patch(&self, rng: &MapRng) -> Option<FloorType> {
+		pick(rng, &self.floorPatches)
+	}
+
+	pub fn shrine(&self, rng: &MapRng) -> Option<Tile> {
+		pick(rng, &self.shrines).map(|ty| landmark_tile(rng, ty))
+	}
+
+	pub fn landmark(&self, rng: &MapRng) -> Option<Tile> {
+		pick(rng, &self.landmarks).map(|ty| landmark_tile(rng, ty))
+	}
+
+	pub fn mob(&self, rng: &MapRng) -> Option<Tile> {
+		pick(rng, &self.mobs).map(|ty| landmark_tile(rng, ty))
+	}
+}
+
+impl Default for Theme {
+	fn default() -> Self {
+		use Landmark::*;
+		Self {
+			rooms: Tileset::all.iter().map(|&tileset| (tileset, 1.0)).collect(),
+			floors: vec![(FloorType::Tileset, 1.0)],
+			corridors: vec![(Tileset::Rock, 1.0)],
+			floorPatches: vec![(FloorType::Slab, 1.0), (FloorType::Black, 0.25)],
+			shrines: vec![(ShrineIdol, 1.0), (ShrineSkulls, 1.0), (ShrineScroll, 1.0)],
+			landmarks: vec![
+				(Well, 1.0),
+				(StatueDragon, 1.0),
+				(StatueFace, 1.0),
+				(StatueBronze, 1.0),
+			],
+			mobs: vec![],
+			ambient: None,
+		}
+	}
+}
+
+/// Picks from a weighted palette. Returns `None` for empty palettes.
+fn pick<T: Copy>(rng: &MapRng, choices: &[Weighted<T>]) -> Option<T> {
+	choices
+		.choose_weighted(&mut *rng.as_mut(), |&(_, weight)| weight)
+		.ok()
+		.map(|&(choice, _)| choice)
+}
+
+fn landmark_tile(rng: &MapRng, ty: Landmark) -> Tile {
+	Tile {
+		ty: TileType::Landmark {
+			ty,
+			flip: rng.as_mut().gen_bool(0.5),
+		},
+		..default()
+	}
+}
@@ -0,0 +1,172 @@
+//! Floors never move, so instead of a sprite per tile they are batched into
+//! one mesh per chunk and texture. Everything that has to sort against actors
+//! (walls, landmarks, doors, ...) stays an [`IsoSprite`].
+
+use bevy::math::Vec3Swizzles;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::sprite::Mesh2dHandle;
+
+use super::*;
+use crate::world_to_iso;
+
+/// Root entity of a chunk's floor meshes. Remembers the floors the meshes
+/// were built from, so only chunks that actually changed are rebuilt.
+#[derive(Component)]
+pub struct ChunkMesh {
+	pub pos: ChunkPos,
+	floors: Box<[Tile]>,
+}
+
+/// One of a chunk's floor meshes, with the tile each quad belongs to in
+/// vertex order.
+#[derive(Component)]
+pub struct FloorMesh {
+	pub tiles: Vec<TilePos>,
+}
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.add_systems(PostUpdate, update_chunk_meshes);
+}
+
+fn update_chunk_meshes(
+	mut cmd: Commands,
+	map: Option<Res<Map>>,
+	assets: Res<AssetServer>,
+	images: Res<Assets<Image>>,
+	mut meshes: ResMut<Assets<Mesh>>,
+	mut materials: ResMut<Assets<ColorMaterial>>,
+	chunkMeshes: Query<(Entity, &ChunkMesh)>,
+	mut materialCache: Local<HashMap<&'static str, Handle<ColorMaterial>>>,
+	mut waitingOnTextures: Local<bool>,
+) {
+	let Some(map) = map else {
+		return;
+	};
+	if !map.is_changed() && !*waitingOnTextures {
+		return;
+	}
+	*waitingOnTextures = false;
+
+	let mut existing: HashMap<_, _> = chunkMeshes
+		.iter()
+		.map(|(ent, chunkMesh)| (chunkMesh.pos, (ent, chunkMesh)))
+		.collect();
+	for (&pos, chunk) in &map.chunks {
+		let floors: Box<[Tile]> = chunk.tiles.iter().map(|pair| pair.background).collect();
+		match existing.remove(&pos) {
+			Some((_, chunkMesh)) if chunkMesh.floors == floors => continue,
+			Some((ent, _)) => cmd.entity(ent).despawn_recursive(),
+			None => {},
+		}
+
+		// tile sizes aren't known until textures are loaded, so try again next frame
+		let Some(batches) = batch_floors(chunk, &assets, &images) else {
+			*waitingOnTextures = true;
+			continue;
+		};
+
+		// the front-most tile of the chunk, i.e. the southwesternmost one
+		let front = TilePos::of(pos.min_tile().x, pos.max_tile().y);
+		let depth = RenderLayer::Floor.depth(front.as_vec2() * tileRadius);
+		cmd.spawn((
+			ChunkMesh { pos, floors },
+			SpatialBundle::from_transform(Transform::from_xyz(0.0, 0.0, depth)),
+		))
+		.with_children(|b| {
+			for (path, floor, mesh) in batches {
+				let material = materialCache
+					.entry(path)
+					.or_insert_with(|| {
+						let texture: Handle<Image> = assets.load(path);
+						materials.add(texture.into())
+					})
+					.clone();
+				b.spawn((
+					floor,
+					MaterialMesh2dBundle {
+						mesh: Mesh2dHandle(meshes.add(mesh)),
+						material,
+						..default()
+					},
+				));
+			}
+		});
+	}
+
+	// chunks removed from the map
+	for (ent, _) in existing.into_values() {
+		cmd.entity(ent).despawn_recursive();
+	}
+}
+
+/// Builds one mesh of the chunk's floors per texture. Returns `None` if a
+/// texture hasn't finished loading yet.
+fn batch_floors(
+	chunk: &Chunk,
+	assets: &AssetServer,
+	images: &Assets<Image>,
+) -> Option<Vec<(&'static str, FloorMesh, Mesh)>> {
+	let mut batches: HashMap<&'static str, Vec<(TilePos, Rect, bool)>> = HashMap::new();
+	for pos in chunk.tile_positions() {
+		let floor = chunk.tiles[pos.chunk_relative().chunk_index()].background;
+		if floor.is_empty() {
+			continue;
+		}
+		let (path, rect, flip) = floor.texture_info();
+		batches.entry(path).or_default().push((pos, rect, flip));
+	}
+
+	batches
+		.into_iter()
+		.map(|(path, mut tiles)| {
+			let texture: Handle<Image> = assets.load(path);
+			let textureSize = images.get(&texture)?.size();
+			// triangles are drawn in order, so go back to front like sorted sprites would
+			tiles.sort_by_key(|(pos, ..)| std::cmp::Reverse(pos.x - pos.y));
+			let mesh = floor_mesh(&tiles, textureSize);
+			let floor = FloorMesh {
+				tiles: tiles.iter().map(|&(pos, ..)| pos).collect(),
+			};
+			Some((path, floor, mesh))
+		})
+		.collect()
+}
+
+/// Builds a mesh of one quad per tile, laid out in isometric screen space the
+/// same way `isosprite_extract` places sprites.
+fn floor_mesh(tiles: &[(TilePos, Rect, bool)], textureSize: Vec2) -> Mesh {
+	let mut positions = Vec::with_capacity(tiles.len() * 4);
+	let mut uvs = Vec::with_capacity(tiles.len() * 4);
+	let mut indices = Vec::with_capacity(tiles.len() * 6);
+	for &(pos, rect, flip) in tiles.iter() {
+		let center = world_to_iso(pos.as_vec2() * tileRadius).xy();
+		let (min, max) = (center - rect.half_size(), center + rect.half_size());
+		let (mut u0, mut u1) = (rect.min.x / textureSize.x, rect.max.x / textureSize.x);
+		if flip {
+			std::mem::swap(&mut u0, &mut u1);
+		}
+		// texture coordinates grow downwards, screen coordinates upwards
+		let (v0, v1) = (rect.max.y / textureSize.y, rect.min.y / textureSize.y);
+
+		let base = positions.len() as u32;
+		positions.extend([
+			[min.x, min.y, 0.0],
+			[max.x, min.y, 0.0],
+			[max.x, max.y, 0.0],
+			[min.x, max.y, 0.0],
+		]);
+		uvs.extend([[u0, v0], [u1, v0], [u1, v1], [u0, v1]]);
+		indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+	}
+
+	let vertexCount = positions.len();
+	let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+	mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+	mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; vertexCount]);
+	mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+	// lit by `light::light_floors`
+	mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, vec![[1.0; 4]; vertexCount]);
+	mesh.set_indices(Some(Indices::U32(indices)));
+	mesh
+}
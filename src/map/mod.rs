@@ -1,5 +1,6 @@
 pub mod data;
 pub mod gen;
+pub mod mesh;
 
 use std::cell::{OnceCell, RefCell, RefMut};
 use std::collections::{HashMap, HashSet, VecDeque};
@@ -14,15 +15,23 @@ use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 use rand::rngs::SmallRng;
 use rand::{thread_rng, Rng, SeedableRng};
-use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 
 pub use self::data::*;
-use crate::{AResult, IsoSprite, IsoSpriteBundle};
+use crate::{AResult, IsoSprite, IsoSpriteBundle, RenderLayer};
 
 pub const tileDiameter: f32 = 64.0;
 pub const tileRadius: f32 = tileDiameter / 2.0;
 
-#[derive(Clone, Copy, Debug, Default, Deserialize)]
+/// Returns the rect of the `index`th tile of a texture atlas `columns` tiles
+/// wide.
+pub fn atlas_rect(index: u32, columns: u32) -> Rect {
+	let min = uvec2(index % columns, index / columns).as_vec2() * tileDiameter;
+	Rect::from_corners(min, min + tileDiameter)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct Tile {
 	pub ty: TileType,
 	#[serde(default)]
@@ -48,23 +57,55 @@ impl Tile {
 			TileType::Landmark { ty, flip } => (Some("tiles/misc.png"), flip, ty as _),
 		};
 		let tilesetWidthElems = texture.map(|_| 16).unwrap_or(8);
-		let index = uvec2(
-			(index % tilesetWidthElems) as _,
-			(index / tilesetWidthElems) as _,
-		) * tileDiameter as u32;
-
 		(
 			texture.unwrap_or(self.tileset.asset_path()),
-			Rect::new(
-				index.x as f32,
-				index.y as f32,
-				index.x as f32 + tileDiameter,
-				index.y as f32 + tileDiameter,
-			),
+			atlas_rect(index, tilesetWidthElems),
 			flip,
 		)
 	}
 
+	/// Whether this tile is tall enough to hide actors standing behind it.
+	pub fn is_occluder(&self) -> bool {
+		match self.ty {
+			TileType::Wall(_) => true,
+			TileType::Landmark { ty, .. } => ty.is_obstacle(),
+			_ => false,
+		}
+	}
+
+	/// Size of the floor area this tile's sprite covers, see
+	/// [`IsoSprite::footprint`].
+	pub fn footprint(&self) -> UVec2 {
+		match self.ty {
+			TileType::Landmark { ty, .. } => ty.footprint(),
+			_ => UVec2::ONE,
+		}
+	}
+
+	pub fn render_layer(&self) -> RenderLayer {
+		match self.ty {
+			TileType::Floor(_) => RenderLayer::Floor,
+			TileType::Landmark {
+				ty: Landmark::TrapArrow | Landmark::TrapPentagram | Landmark::TrapSkull,
+				..
+			} => RenderLayer::FloorDecal,
+			TileType::Landmark {
+				ty:
+					Landmark::ExplosionRed |
+					Landmark::ExplosionBlue |
+					Landmark::ExplosionGreen |
+					Landmark::ExplosionSmokeLight |
+					Landmark::ExplosionSmokeDark,
+				..
+			} => RenderLayer::Overhead,
+			TileType::Landmark {
+				ty: Landmark::Cursor,
+				..
+			} => RenderLayer::WorldUi,
+			_ => RenderLayer::Object,
+		}
+	}
+
 	pub fn into_bundle(
 		self,
 		pos: Vec2,
@@ -74,6 +115,7 @@ impl Tile {
 		let texture = assets.load(texture);
 		let collider = match self.ty {
 			TileType::Wall(shape) => Some(shape.collider()),
+			TileType::Landmark { ty, .. } => ty.collider(),
 			_ => None,
 		};
 
@@ -83,6 +125,8 @@ impl Tile {
 				sprite: IsoSprite {
 					rect,
 					flip,
+					layer: self.render_layer(),
+					footprint: self.footprint(),
 					..default()
 				},
 				transform: Transform::from_translation((pos * tileRadius, 0.0).into()).into(),
@@ -93,14 +137,14 @@ impl Tile {
 	}
 }
 
-#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct TilePair {
 	#[serde(default)]
 	pub foreground: Tile,
 	pub background: Tile,
 
 	/// Whether this tile has been replaced by a dynamic entity.
-	#[serde(default)]
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
 	pub plucked: bool,
 }
 
@@ -128,6 +172,17 @@ impl TilePair {
 		matches!(self.foreground.ty, TileType::Landmark { .. })
 	}
 
+	/// Whether actors can walk through this tile. Doors count as walkable,
+	/// since they can be opened; whether they are is up to their entity.
+	pub fn is_walkable(&self) -> bool {
+		match self.foreground.ty {
+			TileType::Empty => matches!(self.background.ty, TileType::Floor(_)),
+			TileType::DoorNS { .. } | TileType::DoorEW { .. } => true,
+			TileType::Landmark { ty, .. } => !ty.is_obstacle(),
+			TileType::Floor(_) | TileType::Wall(_) => false,
+		}
+	}
+
 	/// Set foreground or background depending on type of `tile`. Clears
 	/// foreground if setting a floor.
 	pub fn set(&mut self, tile: Tile) {
@@ -157,50 +212,48 @@ impl TilePair {
 		self.background.ty = TileType::Empty;
 	}
 
-	pub fn into_entity(self, pos: TilePos, cmd: &mut Commands, assets: &AssetServer) -> Entity {
+	/// Spawns the static entity of the foreground tile, if any. Backgrounds
+	/// are drawn by [chunk meshes](mesh::ChunkMesh) instead.
+	pub fn into_entity(
+		self,
+		pos: TilePos,
+		cmd: &mut Commands,
+		assets: &AssetServer,
+	) -> Option<Entity> {
 		debug_assert!(!self.is_empty(), "Attempting to spawn empty TilePair");
 
-		let Self {
-			mut foreground,
-			background,
-			plucked,
-		} = self;
-		if plucked {
-			// plucked tiles are rendered by dynamic entities
-			foreground.ty = TileType::Empty;
+		// plucked tiles are rendered by dynamic entities
+		// FIXME: eventually floors will sometimes have (sensor) colliders, e.g. lava
+		if self.plucked || self.foreground.is_empty() {
+			return None;
 		}
 
-		let pos = pos.as_vec2();
-		let mut foreground = if foreground.is_empty() {
-			cmd.spawn((
-				TransformBundle::from(Transform::from_translation((pos * tileRadius, 0.0).into())),
-				VisibilityBundle::default(),
-			))
-		} else {
-			let (foreground, collider) = foreground.into_bundle(pos, assets);
-			let mut ent = cmd.spawn(foreground);
-			if let Some(c) = collider {
-				c.insert_into(&mut ent);
-			}
-			ent
-		};
-
-		if !background.is_empty() {
-			let (background, _) = background.into_bundle(pos, assets);
-			// FIXME: eventually floors will sometimes have (sensor) colliders, e.g. lava
-			foreground.with_children(|b| {
-				b.spawn(IsoSpriteBundle {
-					// ensures players, mobs, etc. render over background
-					transform: Transform::from_xyz(0.0, 0.0, -tileDiameter).into(),
-					..background
-				});
-			});
+		let (foreground, collider) = self.foreground.into_bundle(pos.as_vec2(), assets);
+		let mut ent = cmd.spawn((foreground, MapTile(pos)));
+		if let Some(c) = collider {
+			c.insert_into(&mut ent);
 		}
-
-		foreground.id()
+		if self.foreground.is_occluder() {
+			ent.insert(Occluder);
+		}
+		Some(ent.id())
 	}
 }
 
+/// Marks the static entity spawned for the tile at the given position.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct MapTile(pub TilePos);
+
+/// Marks the dynamic entity spawned for a [plucked](MutMap::pluck_tiles) tile
+/// that stays put, e.g. a door.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct PluckedTile(pub TilePos);
+
+/// Marks static tiles that fade out while covering the player, see
+/// [`Tile::is_occluder`].
+#[derive(Clone, Copy, Debug, Default, Component)]
+pub struct Occluder;
+
 #[derive(Clone)]
 pub struct Chunk {
 	pub pos: ChunkPos,
@@ -344,6 +397,43 @@ impl Map {
 		}
 		None
 	}
+
+	/// Finds a shortest walkable path from `from` to `to`, both included,
+	/// avoiding `blocked` tiles such as closed doors. `to` itself doesn't need
+	/// to be walkable, so paths can lead up to obstacles like shrines.
+	pub fn find_path(
+		&self,
+		from: TilePos,
+		to: TilePos,
+		blocked: impl Fn(TilePos) -> bool,
+	) -> Option<Vec<TilePos>> {
+		let bounds = self.used_tiles();
+		let mut parents = HashMap::from([(from, from)]);
+		let mut queue = VecDeque::from([from]);
+		while let Some(tile) = queue.pop_front() {
+			if tile == to {
+				let mut path = vec![tile];
+				let mut tile = tile;
+				while tile != from {
+					tile = parents[&tile];
+					path.push(tile);
+				}
+				path.reverse();
+				return Some(path);
+			}
+
+			for other in tile.von_neumann_neighborhood() {
+				let passable = other == to || (self[other].is_walkable() && !blocked(other));
+				if !bounds.contains(other) || !passable || parents.contains_key(&other) {
+					continue;
+				}
+
+				parents.insert(other, tile);
+				queue.push_back(other);
+			}
+		}
+		None
+	}
 }
 
 impl Index<ChunkPos> for Map {
@@ -540,7 +630,7 @@ impl DerefMut for MutMap {
 	}
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Prefab {
 	key: HashMap<char, TilePair>,
 	map: Vec<Box<str>>,
@@ -548,17 +638,73 @@ pub struct Prefab {
 	size: OnceCell<UVec2>,
 }
 
+/// Returns the directory assets are loaded from.
+pub fn assets_root(assets: &AssetServer) -> AResult<std::path::PathBuf> {
+	let io = assets
+		.asset_io()
+		.downcast_ref::<bevy::asset::FileAssetIo>()
+		.ok_or_else(|| anyhow!("wef"))?;
+	Ok(io.root_path().clone())
+}
+
+/// Synchronously reads and deserializes a RON file from the assets directory.
+pub fn load_ron_blocking<T: DeserializeOwned>(assets: &AssetServer, path: &str) -> AResult<T> {
+	let path = assets_root(assets)?.join(path);
+	let str = std::fs::read_to_string(path)?;
+	Ok(ron::from_str(&str)?)
+}
+
+/// Synchronously serializes `value` into a RON file in the assets directory.
+pub fn save_ron_blocking<T: Serialize>(assets: &AssetServer, path: &str, value: &T) -> AResult<()> {
+	let path = assets_root(assets)?.join(path);
+	let str = ron::ser::to_string_pretty(value, default())?;
+	std::fs::write(path, str)?;
+	Ok(())
+}
+
 impl Prefab {
 	pub fn load_blocking(assets: &AssetServer, path: &str) -> AResult<Self> {
-		let io = assets
-			.asset_io()
-			.downcast_ref::<bevy::asset::FileAssetIo>()
-			.ok_or_else(|| anyhow!("wef"))?;
-		let root = io.root_path();
-		let path = root.join(path);
+		load_ron_blocking(assets, path)
+	}
 
-		let str = std::fs::read_to_string(path)?;
-		Ok(ron::from_str(&str)?)
+	pub fn save_blocking(&self, assets: &AssetServer, path: &str) -> AResult<()> {
+		save_ron_blocking(assets, path, self)
+	}
+
+	/// Captures the tiles of `map` within `rect` as a prefab, with its origin
+	/// at `rect.min`.
+	pub fn from_map(map: &Map, rect: TileRect) -> Self {
+		// printable ASCII, minus the space reserved for empty tiles, then
+		// whatever follows the Latin-1 controls
+		let mut chars = ('!' ..= '~').chain('\u{a1}' ..);
+		let mut key: HashMap<char, TilePair> = HashMap::new();
+		let mut keyOf: HashMap<TilePair, char> = HashMap::new();
+		let mut lines = vec![];
+		for y in rect.min.y ..= rect.max.y {
+			let mut line = String::new();
+			for x in rect.min.x ..= rect.max.x {
+				let mut pair = map[(x, y)];
+				if pair.is_empty() {
+					line.push(' ');
+					continue;
+				}
+
+				pair.plucked = false;
+				let char = *keyOf.entry(pair).or_insert_with(|| {
+					let char = chars.next().unwrap();
+					key.insert(char, pair);
+					char
+				});
+				line.push(char);
+			}
+			lines.push(line.trim_end().into());
+		}
+
+		Self {
+			key,
+			map: lines,
+			size: default(),
+		}
 	}
 
 	pub fn into_map(self, seed: Option<u64>) -> AResult<MutMap> {
@@ -572,12 +718,21 @@ impl Prefab {
 	pub fn size(&self) -> UVec2 {
 		*self.size.get_or_init(|| {
 			UVec2::new(
-				self.map.iter().map(|s| s.len()).max().unwrap_or(0) as _,
+				self.map.iter().map(|s| s.chars().count()).max().unwrap_or(0) as _,
 				self.map.len() as _,
 			)
 		})
 	}
 
+	/// Returns the tile at `pos` (relative to the prefab's origin), if any.
+	pub fn get(&self, pos: TilePos) -> Option<TilePair> {
+		if pos.x < 0 || pos.y < 0 {
+			return None;
+		}
+		let char = self.map.get(pos.y as usize)?.chars().nth(pos.x as usize)?;
+		(char != ' ').then(|| self.key[&char])
+	}
+
 	pub fn iter(&self) -> impl '_ + Iterator<Item = (TilePos, TilePair)> {
 		self.map.iter().enumerate().flat_map(move |(y, line)| {
 			line.chars().enumerate().filter_map(move |(x, char)| {
@@ -0,0 +1,433 @@
+//! Game states and the menus between them: the main menu on launch, the pause
+//! menu (Escape by default), and the save, load and settings screens reachable
+//! from them.
+//! Time and physics stand still outside of [`GameState::Playing`], and
+//! [`update_actions`](crate::input) stops passing on gameplay input.
+
+use std::fmt::Write;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::window::ReceivedCharacter;
+use bevy_rapier2d::prelude::RapierConfiguration;
+use rand::{thread_rng, Rng};
+
+use crate::entities::player::{self, Player};
+use crate::feed::{FeedCategory, FeedEntry, Severity};
+use crate::input::{Action, BindingsScreen};
+use crate::map::{gen, Map};
+use crate::save::{self, SaveMeta, SaveSlot};
+use crate::settings::{Settings, UserConfig};
+use crate::LevelInfo;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, States)]
+pub enum GameState {
+	#[default]
+	MainMenu,
+	Playing,
+	Paused,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Page {
+	Main,
+	Pause,
+	Save,
+	Load,
+	Settings,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Item {
+	NewGame,
+	Continue,
+	Settings,
+	Quit,
+	Resume,
+	SaveGame,
+	LoadGame,
+	QuitToMenu,
+	SaveTo(SaveSlot),
+	LoadFrom(SaveSlot),
+	Resolution,
+	Fullscreen,
+	Volume,
+	KeyBindings,
+	Back,
+}
+
+/// Menu navigation: Up/Down pick an item, Enter activates it, Left/Right
+/// change settings. On the main menu, typing digits edits the seed of the
+/// next new game.
+#[derive(Resource)]
+struct Menu {
+	page: Page,
+	selected: usize,
+	seed: String,
+	/// Existing saves, newest first. Refreshed when entering a menu and after
+	/// saving.
+	saves: Vec<(SaveSlot, SaveMeta)>,
+}
+
+impl Menu {
+	fn items(&self, inGame: bool) -> Vec<Item> {
+		use Item::*;
+		let hasSaves = !self.saves.is_empty();
+		let mut items = match self.page {
+			Page::Main => vec![NewGame, Continue, LoadGame, Settings, Quit],
+			Page::Pause => vec![Resume, SaveGame, LoadGame, Settings, QuitToMenu, Quit],
+			Page::Save => (1 ..= SaveSlot::manualCount)
+				.map(|index| SaveTo(SaveSlot::Manual(index)))
+				.chain([Back])
+				.collect(),
+			Page::Load => self
+				.saves
+				.iter()
+				.map(|&(slot, _)| LoadFrom(slot))
+				.chain([Back])
+				.collect(),
+			Page::Settings => vec![Resolution, Fullscreen, Volume, KeyBindings, Back],
+		};
+		items.retain(|&item| match item {
+			Continue => inGame || hasSaves,
+			LoadGame => hasSaves,
+			_ => true,
+		});
+		items
+	}
+
+	fn save_meta(&self, slot: SaveSlot) -> Option<&SaveMeta> {
+		self.saves
+			.iter()
+			.find(|&&(other, _)| other == slot)
+			.map(|(_, meta)| meta)
+	}
+
+	fn open(&mut self, page: Page) {
+		self.page = page;
+		self.selected = 0;
+	}
+}
+
+#[derive(Component)]
+struct MenuNode;
+
+#[derive(Component)]
+struct MenuText;
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.add_state::<GameState>();
+	app.insert_resource(Menu {
+		page: Page::Main,
+		selected: 0,
+		seed: String::new(),
+		saves: vec![],
+	});
+	app.add_systems(Startup, startup_gui);
+	app.add_systems(OnEnter(GameState::MainMenu), enter_main_menu);
+	app.add_systems(OnEnter(GameState::Paused), enter_pause_menu);
+	app.add_systems(OnEnter(GameState::Playing), (resume_world, hide_gui));
+	app.add_systems(OnExit(GameState::Playing), pause_world);
+	app.add_systems(
+		Update,
+		(
+			pause_game.run_if(in_state(GameState::Playing)),
+			(navigate, update_gui)
+				.chain()
+				.run_if(not(in_state(GameState::Playing))),
+		),
+	);
+}
+
+fn startup_gui(mut cmd: Commands, assets: Res<AssetServer>) {
+	cmd.spawn((
+		MenuNode,
+		NodeBundle {
+			style: Style {
+				width: Val::Percent(100.0),
+				height: Val::Percent(100.0),
+				position_type: PositionType::Absolute,
+				align_items: AlignItems::Center,
+				justify_content: JustifyContent::Center,
+				..default()
+			},
+			background_color: BackgroundColor(Color::rgba(0.0, 0.0, 0.0, 0.8)),
+			// above the rest of the UI
+			z_index: ZIndex::Global(10),
+			..default()
+		},
+	))
+	.with_children(|parent| {
+		parent.spawn((
+			MenuText,
+			TextBundle::from_section(
+				"",
+				TextStyle {
+					font: assets.load("fonts/RedHatDisplay.ttf"),
+					font_size: 40.0,
+					color: Color::WHITE,
+				},
+			),
+		));
+	});
+}
+
+fn hide_gui(mut node: Query<&mut Visibility, With<MenuNode>>) {
+	*node.single_mut() = Visibility::Hidden;
+}
+
+fn enter_main_menu(mut menu: ResMut<Menu>, config: Res<UserConfig>) {
+	menu.open(Page::Main);
+	menu.seed = thread_rng().gen::<u32>().to_string();
+	menu.saves = save::list_saves(&config);
+}
+
+fn enter_pause_menu(mut menu: ResMut<Menu>, config: Res<UserConfig>) {
+	menu.open(Page::Pause);
+	menu.saves = save::list_saves(&config);
+}
+
+fn pause_world(mut time: ResMut<Time>, mut rapier: ResMut<RapierConfiguration>) {
+	time.pause();
+	rapier.physics_pipeline_active = false;
+}
+
+fn resume_world(mut time: ResMut<Time>, mut rapier: ResMut<RapierConfiguration>) {
+	time.unpause();
+	rapier.physics_pipeline_active = true;
+}
+
+fn pause_game(
+	actions: Res<Input<Action>>,
+	mut bindings: ResMut<BindingsScreen>,
+	mut nextState: ResMut<NextState<GameState>>,
+) {
+	if !actions.just_pressed(Action::Pause) {
+		return;
+	}
+	// close the bindings screen first
+	if bindings.open {
+		bindings.open = false;
+	} else {
+		nextState.set(GameState::Paused);
+	}
+}
+
+/// Generates the first level from `seed` and starts playing it.
+fn start_new_game(world: &mut World, seed: u64) {
+	let assets = world.resource::<AssetServer>().clone();
+	match gen::generate_level(&assets, 0, seed) {
+		Ok(map) => {
+			crate::load_level(world, LevelInfo { level: 0, seed }, map);
+			world.resource_mut::<NextState<GameState>>().set(GameState::Playing);
+			FeedEntry::new(
+				"Your nose is assaulted by a musty breeze, as you enter... The Undercity.",
+			)
+			.submit();
+		},
+		Err(err) => {
+			error!("failed to generate level from seed {seed}: {err:#}");
+			FeedEntry::new(format!("Could not generate a level: {err}"))
+				.category(FeedCategory::System)
+				.severity(Severity::Bad)
+				.submit();
+		},
+	}
+}
+
+fn navigate(
+	mut cmd: Commands,
+	actions: Res<Input<Action>>,
+	keyboard: Res<Input<KeyCode>>,
+	mut chars: EventReader<ReceivedCharacter>,
+	state: Res<State<GameState>>,
+	mut nextState: ResMut<NextState<GameState>>,
+	mut menu: ResMut<Menu>,
+	mut bindings: ResMut<BindingsScreen>,
+	mut settings: ResMut<Settings>,
+	config: Res<UserConfig>,
+	map: Option<Res<Map>>,
+	player: Query<Entity, With<Player>>,
+	mut exit: EventWriter<AppExit>,
+) {
+	// the bindings screen uses the same keys
+	if bindings.open {
+		if actions.just_pressed(Action::Pause) {
+			bindings.open = false;
+		}
+		chars.clear();
+		return;
+	}
+
+	let backPage = if *state.get() == GameState::MainMenu {
+		Page::Main
+	} else {
+		Page::Pause
+	};
+	let items = menu.items(map.is_some());
+	menu.selected = menu.selected.min(items.len() - 1);
+	let item = items[menu.selected];
+
+	if menu.page == Page::Main {
+		for ev in chars.iter().filter(|ev| ev.char.is_ascii_digit()) {
+			// stay within u64
+			if menu.seed.len() < 19 {
+				menu.seed.push(ev.char);
+			}
+		}
+		if keyboard.just_pressed(KeyCode::Back) {
+			menu.seed.pop();
+		}
+	} else {
+		chars.clear();
+	}
+
+	if actions.just_pressed(Action::MenuUp) {
+		menu.selected = menu.selected.checked_sub(1).unwrap_or(items.len() - 1);
+	}
+	if actions.just_pressed(Action::MenuDown) {
+		menu.selected = (menu.selected + 1) % items.len();
+	}
+	if actions.just_pressed(Action::Pause) {
+		match menu.page {
+			Page::Main => {},
+			Page::Pause => nextState.set(GameState::Playing),
+			Page::Save | Page::Load | Page::Settings => menu.open(backPage),
+		}
+		return;
+	}
+
+	let left = actions.just_pressed(Action::MenuLeft);
+	let right = actions.just_pressed(Action::MenuRight);
+	let enter = actions.just_pressed(Action::MenuSelect);
+	let step = right as i32 - left as i32;
+	let changed = match item {
+		Item::Resolution if step != 0 || enter => {
+			let all = Settings::resolutions;
+			let current = all.iter().position(|&res| res == settings.resolution);
+			// Enter cycles forwards
+			let step = if step == 0 { 1 } else { step };
+			let next = match current {
+				Some(i) => (i as i32 + step).rem_euclid(all.len() as i32),
+				None => 0,
+			};
+			settings.resolution = all[next as usize];
+			true
+		},
+		Item::Fullscreen if step != 0 || enter => {
+			settings.fullscreen = !settings.fullscreen;
+			true
+		},
+		Item::Volume if step != 0 => {
+			settings.volume = (settings.volume + 0.1 * step as f32).clamp(0.0, 1.0);
+			true
+		},
+		_ => false,
+	};
+	if changed {
+		if let Err(err) = settings.save_blocking(&config) {
+			FeedEntry::new(format!("Could not save settings: {err}"))
+				.category(FeedCategory::System)
+				.severity(Severity::Warning)
+				.submit();
+		}
+		return;
+	}
+	if !enter {
+		return;
+	}
+
+	match item {
+		Item::NewGame => {
+			let seed = menu.seed.parse().unwrap_or_else(|_| thread_rng().gen());
+			player::reset(&mut cmd, player.single());
+			cmd.add(move |world: &mut World| start_new_game(world, seed));
+		},
+		Item::Continue if map.is_none() => {
+			let (latest, _) = menu.saves[0];
+			cmd.add(move |world: &mut World| save::load_and_report(world, latest));
+		},
+		Item::Continue | Item::Resume => nextState.set(GameState::Playing),
+		Item::SaveGame => menu.open(Page::Save),
+		Item::LoadGame => menu.open(Page::Load),
+		Item::SaveTo(slot) => {
+			cmd.add(move |world: &mut World| {
+				save::save_and_report(world, slot);
+				let saves = save::list_saves(world.resource());
+				world.resource_mut::<Menu>().saves = saves;
+			});
+		},
+		Item::LoadFrom(slot) => {
+			cmd.add(move |world: &mut World| save::load_and_report(world, slot));
+		},
+		Item::Settings => menu.open(Page::Settings),
+		Item::Quit => exit.send(AppExit),
+		Item::QuitToMenu => nextState.set(GameState::MainMenu),
+		Item::KeyBindings => bindings.open = true,
+		Item::Back => menu.open(backPage),
+		Item::Resolution | Item::Fullscreen | Item::Volume => {},
+	}
+}
+
+fn update_gui(
+	menu: Res<Menu>,
+	settings: Res<Settings>,
+	bindings: Res<BindingsScreen>,
+	map: Option<Res<Map>>,
+	mut node: Query<&mut Visibility, With<MenuNode>>,
+	mut text: Query<&mut Text, With<MenuText>>,
+) {
+	// make way for the bindings screen
+	*node.single_mut() = if bindings.open {
+		Visibility::Hidden
+	} else {
+		Visibility::Inherited
+	};
+
+	let mut value = String::new();
+	let title = match menu.page {
+		Page::Main => "THE UNDERCITY",
+		Page::Pause => "PAUSED",
+		Page::Save => "SAVE GAME",
+		Page::Load => "LOAD GAME",
+		Page::Settings => "SETTINGS",
+	};
+	writeln!(value, "{title}\n").unwrap();
+	for (i, item) in menu.items(map.is_some()).into_iter().enumerate() {
+		let marker = if i == menu.selected { "> " } else { "  " };
+		value.push_str(marker);
+		match item {
+			Item::NewGame => write!(value, "New game  (seed: {}_)", menu.seed),
+			Item::Continue => write!(value, "Continue"),
+			Item::Settings => write!(value, "Settings"),
+			Item::Quit => write!(value, "Quit"),
+			Item::Resume => write!(value, "Resume"),
+			Item::SaveGame => write!(value, "Save game"),
+			Item::LoadGame => write!(value, "Load game"),
+			Item::QuitToMenu => write!(value, "Quit to main menu"),
+			Item::SaveTo(slot) | Item::LoadFrom(slot) => {
+				let summary = menu.save_meta(slot).map_or("empty".into(), SaveMeta::summary);
+				write!(value, "{}:  {summary}", slot.name())
+			},
+			Item::Resolution => {
+				let (width, height) = settings.resolution;
+				write!(value, "Resolution:  < {width}x{height} >")
+			},
+			Item::Fullscreen => {
+				let fullscreen = if settings.fullscreen { "on" } else { "off" };
+				write!(value, "Fullscreen:  {fullscreen}")
+			},
+			Item::Volume => write!(value, "Volume:  < {:.0}% >", settings.volume * 100.0),
+			Item::KeyBindings => write!(value, "Key bindings..."),
+			Item::Back => write!(value, "Back"),
+		}
+		.unwrap();
+		value.push('\n');
+	}
+
+	let section = &mut text.single_mut().sections[0].value;
+	if *section != value {
+		*section = value;
+	}
+}
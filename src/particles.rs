@@ -0,0 +1,292 @@
+//! Lightweight particles. Each [`ParticleEmitter`] simulates its particles in
+//! world space and extracts them as sprites through the same isometric
+//! projection as [`IsoSprite`](crate::IsoSprite)s. Presets are RON files in
+//! `assets/particles`.
+
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::sync::Arc;
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy::render::{Extract, RenderApp};
+use bevy::sprite::{ExtractedSprite, ExtractedSprites, SpriteSystem};
+use rand::{thread_rng, Rng};
+use serde::Deserialize;
+
+use crate::map::{atlas_rect, load_ron_blocking, tileRadius, FloorType, MutMap, TileType};
+use crate::{world_to_iso, AResult, RenderLayer};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ParticlePreset {
+	pub texture: String,
+	/// Width of the texture atlas in tiles.
+	pub columns: u32,
+	/// Atlas indices particles pick their sprite from.
+	pub frames: Vec<u32>,
+	pub layer: RenderLayer,
+	/// Particles spawned as soon as the emitter appears.
+	pub burst: u32,
+	/// Particles spawned per second after the burst. Emitters without a rate
+	/// despawn once their burst has died out.
+	pub rate: f32,
+	/// Radius around the emitter particles spawn in, in tiles.
+	pub spawnRadius: f32,
+	/// Range of particle lifetimes in seconds.
+	pub lifetime: (f32, f32),
+	/// Range of speeds along the floor in a random direction, in tiles per
+	/// second.
+	pub speed: (f32, f32),
+	/// Range of upwards speeds, in tiles per second.
+	pub rise: (f32, f32),
+	/// Upwards acceleration in tiles per second squared; negative to fall.
+	pub gravity: f32,
+	/// Fraction of velocity lost per second.
+	pub drag: f32,
+	/// Sprite scale at birth and at death.
+	pub scale: (f32, f32),
+	/// RGBA tint at birth and at death.
+	pub color: ([f32; 4], [f32; 4]),
+}
+
+impl Default for ParticlePreset {
+	fn default() -> Self {
+		Self {
+			texture: "tiles/misc.png".into(),
+			columns: 16,
+			frames: vec![],
+			layer: RenderLayer::Overhead,
+			burst: 0,
+			rate: 0.0,
+			spawnRadius: 0.0,
+			lifetime: (1.0, 1.0),
+			speed: (0.0, 0.0),
+			rise: (0.0, 0.0),
+			gravity: 0.0,
+			drag: 0.0,
+			scale: (1.0, 1.0),
+			color: ([1.0; 4], [1.0; 4]),
+		}
+	}
+}
+
+impl ParticlePreset {
+	pub fn load_blocking(assets: &AssetServer, path: &str) -> AResult<Self> {
+		load_ron_blocking(assets, path)
+	}
+
+	fn spawn(&self, origin: Vec2, rng: &mut impl Rng) -> Particle {
+		let offset = Vec2::from_angle(rng.gen_range(0.0 .. TAU)) *
+			rng.gen_range(0.0 ..= self.spawnRadius) *
+			tileRadius;
+		let dir = Vec2::from_angle(rng.gen_range(0.0 .. TAU));
+		let index = self.frames[rng.gen_range(0 .. self.frames.len())];
+		Particle {
+			pos: origin + offset,
+			height: 0.0,
+			vel: dir * sample(rng, self.speed) * tileRadius,
+			rise: sample(rng, self.rise) * tileRadius,
+			age: 0.0,
+			lifetime: sample(rng, self.lifetime).max(f32::EPSILON),
+			rect: atlas_rect(index, self.columns),
+			flip: rng.gen_bool(0.5),
+		}
+	}
+}
+
+/// Picks a value from an inclusive `(min, max)` range.
+fn sample(rng: &mut impl Rng, (min, max): (f32, f32)) -> f32 {
+	if min < max {
+		rng.gen_range(min ..= max)
+	} else {
+		min
+	}
+}
+
+/// Presets loaded so far, by asset path.
+#[derive(Default, Resource)]
+pub struct ParticlePresets(HashMap<String, Arc<ParticlePreset>>);
+
+impl ParticlePresets {
+	/// Returns the preset at `path`, loading it on first use. Presets that
+	/// fail to load are logged and replaced by one that emits nothing.
+	pub fn get(&mut self, assets: &AssetServer, path: &str) -> Arc<ParticlePreset> {
+		self.0
+			.entry(path.into())
+			.or_insert_with(|| {
+				let preset = ParticlePreset::load_blocking(assets, path).unwrap_or_else(|err| {
+					error!("failed to load particles {path:?}: {err:#}");
+					default()
+				});
+				Arc::new(preset)
+			})
+			.clone()
+	}
+}
+
+#[derive(Clone, Debug)]
+struct Particle {
+	/// Position on the floor, in world space.
+	pos: Vec2,
+	/// Height above the floor, in screen space.
+	height: f32,
+	vel: Vec2,
+	rise: f32,
+	age: f32,
+	lifetime: f32,
+	rect: Rect,
+	flip: bool,
+}
+
+#[derive(Clone, Debug, Component)]
+pub struct ParticleEmitter {
+	pub preset: Arc<ParticlePreset>,
+	particles: Vec<Particle>,
+	bursted: bool,
+	/// Fractional particles owed by `rate`.
+	pending: f32,
+}
+
+impl ParticleEmitter {
+	pub fn new(preset: Arc<ParticlePreset>) -> Self {
+		Self {
+			preset,
+			particles: vec![],
+			bursted: false,
+			pending: 0.0,
+		}
+	}
+}
+
+/// Spawns an emitter of `preset` at `pos` (in world space.) One-off presets
+/// despawn on their own.
+pub fn spawn_particles(
+	cmd: &mut Commands,
+	assets: &AssetServer,
+	preset: Arc<ParticlePreset>,
+	pos: Vec2,
+) -> Entity {
+	cmd.spawn((
+		assets.load::<Image, _>(preset.texture.as_str()),
+		ParticleEmitter::new(preset),
+		TransformBundle::from_transform(Transform::from_translation((pos, 0.0).into())),
+	))
+	.id()
+}
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.init_resource::<ParticlePresets>();
+	app.add_systems(Update, update_emitters);
+	// there's nothing to extract into when running headless
+	if let Ok(renderApp) = app.get_sub_app_mut(RenderApp) {
+		renderApp.add_systems(
+			ExtractSchedule,
+			particle_extract.after(SpriteSystem::ExtractSprites),
+		);
+	}
+}
+
+#[linkme::distributed_slice(crate::setupMap)]
+fn setup_map(map: &mut MutMap, cmd: &mut Commands, assets: &AssetServer) {
+	// only some lava tiles bubble, so pools don't turn into a wall of particles
+	const bubblingChance: f64 = 0.2;
+
+	let mut presets = HashMap::new();
+	for pos in map.used_tiles().tiles() {
+		let path = match map[pos].background.ty {
+			TileType::Floor(FloorType::LavaRed) => "particles/lava_red.ron",
+			TileType::Floor(FloorType::LavaBlue) => "particles/lava_blue.ron",
+			TileType::Floor(FloorType::LavaCyan) => "particles/lava_cyan.ron",
+			_ => continue,
+		};
+		if !map.rng().gen_bool(bubblingChance) {
+			continue;
+		}
+
+		let preset = presets.entry(path).or_insert_with(|| {
+			ParticlePreset::load_blocking(assets, path)
+				.map(Arc::new)
+				.map_err(|err| error!("failed to load particles {path:?}: {err:#}"))
+				.ok()
+		});
+		if let Some(preset) = preset {
+			spawn_particles(cmd, assets, preset.clone(), pos.as_vec2() * tileRadius);
+		}
+	}
+}
+
+fn update_emitters(
+	mut cmd: Commands,
+	time: Res<Time>,
+	mut emitters: Query<(Entity, &mut ParticleEmitter, &GlobalTransform)>,
+) {
+	let dt = time.delta_seconds();
+	let mut rng = thread_rng();
+	for (ent, mut emitter, transform) in &mut emitters {
+		let emitter = &mut *emitter;
+		let preset = &emitter.preset;
+		let origin = transform.translation().xy();
+
+		for particle in &mut emitter.particles {
+			particle.age += dt;
+			let damping = (1.0 - preset.drag * dt).max(0.0);
+			particle.vel *= damping;
+			particle.rise = particle.rise * damping + preset.gravity * tileRadius * dt;
+			particle.pos += particle.vel * dt;
+			particle.height += particle.rise * dt;
+		}
+		emitter.particles.retain(|particle| particle.age < particle.lifetime);
+
+		let mut count = 0;
+		if !emitter.bursted {
+			emitter.bursted = true;
+			count += preset.burst;
+		}
+		emitter.pending += preset.rate * dt;
+		count += emitter.pending as u32;
+		emitter.pending = emitter.pending.fract();
+		if !preset.frames.is_empty() {
+			for _ in 0 .. count {
+				emitter.particles.push(preset.spawn(origin, &mut rng));
+			}
+		}
+
+		if preset.rate <= 0.0 && emitter.particles.is_empty() {
+			cmd.entity(ent).despawn_recursive();
+		}
+	}
+}
+
+fn particle_extract(
+	emitters: Extract<Query<(Entity, &ParticleEmitter, &Handle<Image>)>>,
+	mut extractedSprites: ResMut<ExtractedSprites>,
+) {
+	let lerp = |(a, b): ([f32; 4], [f32; 4]), t: f32| {
+		Color::rgba_from_array(Vec4::from(a).lerp(Vec4::from(b), t))
+	};
+
+	for (entity, emitter, texture) in emitters.iter() {
+		let preset = &emitter.preset;
+		for particle in &emitter.particles {
+			let t = particle.age / particle.lifetime;
+			let scale = preset.scale.0 + (preset.scale.1 - preset.scale.0) * t;
+
+			let mut pos = world_to_iso(particle.pos);
+			pos.y += particle.height;
+			pos.z = preset.layer.depth(particle.pos);
+			extractedSprites.sprites.push(ExtractedSprite {
+				entity,
+				transform: GlobalTransform::from_translation(pos),
+				color: lerp(preset.color, t),
+				rect: Some(particle.rect),
+				custom_size: Some(particle.rect.size() * scale),
+				image_handle_id: texture.id(),
+				flip_x: particle.flip,
+				flip_y: false,
+				anchor: Vec2::ZERO, // center
+			});
+		}
+	}
+}
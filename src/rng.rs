@@ -0,0 +1,73 @@
+//! Randomness of gameplay. Gameplay rolls, like where a shrine blinks to,
+//! draw from [`GameRng`] rather than `thread_rng()`, so a level plays out the
+//! same for the same seed and inputs, and a loaded game carries on as the
+//! saved one would have. Cosmetics such as particles, sounds and sprite
+//! flips keep using `thread_rng()`, as how much they draw depends on frame
+//! rate and volume.
+
+use bevy::prelude::*;
+use rand::rngs::SmallRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+use crate::save::{Restore, SaveDir, Serializer};
+use crate::AResult;
+
+/// Reseeded from the level seed whenever a level is loaded.
+#[derive(Clone, Debug, Resource)]
+pub struct GameRng(SmallRng);
+
+impl GameRng {
+	pub fn new(seed: u64) -> Self {
+		Self(SmallRng::seed_from_u64(seed))
+	}
+}
+
+impl Default for GameRng {
+	fn default() -> Self {
+		Self(SmallRng::from_entropy())
+	}
+}
+
+impl RngCore for GameRng {
+	fn next_u32(&mut self) -> u32 {
+		self.0.next_u32()
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		self.0.next_u64()
+	}
+
+	fn fill_bytes(&mut self, dest: &mut [u8]) {
+		self.0.fill_bytes(dest);
+	}
+
+	fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+		self.0.try_fill_bytes(dest)
+	}
+}
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.init_resource::<GameRng>();
+}
+
+#[linkme::distributed_slice(crate::save::serializers)]
+static serializer: Serializer = Serializer {
+	name: "rng",
+	save: save_rng,
+	load: load_rng,
+};
+
+/// The generator's state can't be written out, so saving reseeds it and
+/// saves the seed; the running game and the save go on alike.
+fn save_rng(world: &mut World, dir: &SaveDir) -> AResult<()> {
+	let mut rng = world.resource_mut::<GameRng>();
+	let seed = rng.gen();
+	*rng = GameRng::new(seed);
+	dir.write("rng", &seed)
+}
+
+fn load_rng(dir: &SaveDir) -> AResult<Restore> {
+	let seed: u64 = dir.read("rng")?;
+	Ok(Box::new(move |world: &mut World| world.insert_resource(GameRng::new(seed))))
+}
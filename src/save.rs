@@ -0,0 +1,272 @@
+//! Save games. A save is a directory under `saves` in the [`UserConfig`]
+//! directory, holding a [`SaveMeta`], the map's tiles, and whatever the
+//! [`serializers`] of other modules write next to them: the player, doors,
+//! shrines, the feed history and so on. Loading reads every file first, so a
+//! broken save leaves the current game alone, then rebuilds the level from
+//! the saved tiles and lets every serializer restore its state on top.
+
+use std::fs;
+use std::iter;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{ensure, Context};
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::feed::{FeedCategory, FeedEntry, Severity};
+use crate::input::Action;
+use crate::map::{Map, MutMap, Prefab, TilePos};
+use crate::menu::GameState;
+use crate::settings::UserConfig;
+use crate::{AResult, LevelInfo};
+
+/// Version of the save format. Bump it whenever saved data changes shape;
+/// saves of other versions are refused rather than loaded wrong.
+pub const saveVersion: u32 = 3;
+
+/// A module's part of save games, registered with
+/// `#[linkme::distributed_slice(crate::save::serializers)]`.
+pub struct Serializer {
+	/// Name of the file the module writes, for error messages.
+	pub name: &'static str,
+	pub save: fn(&mut World, &SaveDir) -> AResult<()>,
+	/// Reads the module's files without touching the game, returning how to
+	/// restore its state once the level has been rebuilt from the saved map.
+	pub load: fn(&SaveDir) -> AResult<Restore>,
+}
+
+/// Restores a module's state from what its [`Serializer`] read.
+pub type Restore = Box<dyn FnOnce(&mut World)>;
+
+#[linkme::distributed_slice]
+pub static serializers: [Serializer] = [..];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveSlot {
+	/// Written by the quicksave key.
+	Quick,
+	/// Picked on the save screen of the pause menu, from 1 to
+	/// [`Self::manualCount`].
+	Manual(u8),
+}
+
+impl SaveSlot {
+	pub const manualCount: u8 = 3;
+
+	pub fn all() -> impl Iterator<Item = Self> {
+		iter::once(Self::Quick).chain((1 ..= Self::manualCount).map(Self::Manual))
+	}
+
+	pub fn name(self) -> String {
+		match self {
+			Self::Quick => "Quicksave".into(),
+			Self::Manual(index) => format!("Slot {index}"),
+		}
+	}
+
+	/// Path of the save directory, relative to the [`UserConfig`] directory.
+	fn dir(self) -> String {
+		match self {
+			Self::Quick => "saves/quick".into(),
+			Self::Manual(index) => format!("saves/slot{index}"),
+		}
+	}
+
+	pub fn meta(self, config: &UserConfig) -> AResult<SaveMeta> {
+		SaveDir::open(config, self)?.read("meta")
+	}
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SaveMeta {
+	pub version: u32,
+	/// Seconds since the Unix epoch.
+	pub savedAt: u64,
+	pub level: LevelInfo,
+}
+
+impl SaveMeta {
+	/// Short description for save listings, e.g. "level 1, 5 min ago".
+	pub fn summary(&self) -> String {
+		let ago = unix_time().saturating_sub(self.savedAt);
+		let ago = match ago {
+			0 ..= 59 => "just now".into(),
+			60 ..= 3599 => format!("{} min ago", ago / 60),
+			3600 ..= 86399 => format!("{} h ago", ago / 3600),
+			_ => format!("{} days ago", ago / 86400),
+		};
+		format!("level {}, {ago}", self.level.level + 1)
+	}
+}
+
+fn unix_time() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map_or(0, |time| time.as_secs())
+}
+
+/// Returns the saves that exist, newest first.
+pub fn list_saves(config: &UserConfig) -> Vec<(SaveSlot, SaveMeta)> {
+	let mut res: Vec<_> = SaveSlot::all()
+		.filter_map(|slot| Some((slot, slot.meta(config).ok()?)))
+		.collect();
+	res.sort_by_key(|(_, meta)| u64::MAX - meta.savedAt);
+	res
+}
+
+/// A save directory, for serializers to write their files into and read
+/// them back from.
+pub struct SaveDir {
+	path: PathBuf,
+}
+
+impl SaveDir {
+	fn open(config: &UserConfig, slot: SaveSlot) -> AResult<Self> {
+		let dir = config.dir.as_ref().context("no user directory to keep saves in")?;
+		Ok(Self {
+			path: dir.join(slot.dir()),
+		})
+	}
+
+	/// Writes `value` to `<name>.ron`.
+	pub fn write<T: Serialize>(&self, name: &str, value: &T) -> AResult<()> {
+		let str = ron::ser::to_string_pretty(value, default())?;
+		fs::write(self.path.join(name).with_extension("ron"), str)?;
+		Ok(())
+	}
+
+	/// Reads back what [`Self::write`] wrote to `<name>.ron`.
+	pub fn read<T: DeserializeOwned>(&self, name: &str) -> AResult<T> {
+		let path = self.path.join(name).with_extension("ron");
+		let str = fs::read_to_string(&path).with_context(|| format!("reading {path:?}"))?;
+		Ok(ron::from_str(&str)?)
+	}
+}
+
+/// The map's tiles, including plucked ones, so that loading respawns doors,
+/// shrines and the like through the usual `setupMap` hooks.
+#[derive(Deserialize, Serialize)]
+struct MapSave {
+	origin: TilePos,
+	tiles: Prefab,
+}
+
+/// Saves the current game to `slot`, replacing what was there.
+pub fn save_game(world: &mut World, slot: SaveSlot) -> AResult<()> {
+	let level = *world.get_resource::<LevelInfo>().context("no game to save")?;
+	let map = world.resource::<Map>();
+	let rect = map.used_tiles();
+	let mapSave = MapSave {
+		origin: rect.min,
+		tiles: Prefab::from_map(map, rect),
+	};
+
+	// written next to the slot and swapped in when complete, so a failed save
+	// leaves the previous one intact
+	let dir = SaveDir::open(world.resource(), slot)?;
+	let partial = SaveDir {
+		path: dir.path.with_extension("partial"),
+	};
+	if partial.path.exists() {
+		fs::remove_dir_all(&partial.path)?;
+	}
+	fs::create_dir_all(&partial.path)?;
+
+	let meta = SaveMeta {
+		version: saveVersion,
+		savedAt: unix_time(),
+		level,
+	};
+	partial.write("meta", &meta)?;
+	partial.write("map", &mapSave)?;
+	for serializer in serializers {
+		(serializer.save)(world, &partial)
+			.with_context(|| format!("saving {}", serializer.name))?;
+	}
+
+	if dir.path.exists() {
+		fs::remove_dir_all(&dir.path)?;
+	}
+	fs::rename(&partial.path, &dir.path)?;
+	Ok(())
+}
+
+/// Replaces the current game, if any, with the one saved in `slot`.
+pub fn load_game(world: &mut World, slot: SaveSlot) -> AResult<()> {
+	let dir = SaveDir::open(world.resource(), slot)?;
+	let meta: SaveMeta = dir.read("meta")?;
+	ensure!(
+		meta.version == saveVersion,
+		"save is from version {} of the save format, expected {saveVersion}",
+		meta.version
+	);
+	let mapSave: MapSave = dir.read("map")?;
+	let restores = serializers
+		.iter()
+		.map(|serializer| {
+			(serializer.load)(&dir).with_context(|| format!("loading {}", serializer.name))
+		})
+		.collect::<AResult<Vec<_>>>()?;
+
+	// nothing changes until everything has been read
+	let mut map = MutMap::new(Some(meta.level.seed));
+	mapSave.tiles.copy_into(&mut map, mapSave.origin);
+	crate::load_level(world, meta.level, map);
+	for restore in restores {
+		restore(world);
+	}
+	Ok(())
+}
+
+/// Saves to `slot`, reporting the outcome in the feed.
+pub fn save_and_report(world: &mut World, slot: SaveSlot) {
+	match save_game(world, slot) {
+		Ok(()) => {
+			FeedEntry::new(format!("Game saved to {}.", slot.name()))
+				.category(FeedCategory::System)
+				.submit();
+		},
+		Err(err) => {
+			error!("failed to save to {slot:?}: {err:#}");
+			FeedEntry::new(format!("Could not save the game: {err:#}"))
+				.category(FeedCategory::System)
+				.severity(Severity::Bad)
+				.submit();
+		},
+	}
+}
+
+/// Loads `slot` and resumes play, reporting the outcome in the feed.
+pub fn load_and_report(world: &mut World, slot: SaveSlot) {
+	match load_game(world, slot) {
+		Ok(()) => {
+			world.resource_mut::<NextState<GameState>>().set(GameState::Playing);
+			FeedEntry::new(format!("Loaded {}.", slot.name()))
+				.category(FeedCategory::System)
+				.submit();
+		},
+		Err(err) => {
+			error!("failed to load {slot:?}: {err:#}");
+			FeedEntry::new(format!("Could not load the game: {err:#}"))
+				.category(FeedCategory::System)
+				.severity(Severity::Bad)
+				.submit();
+		},
+	}
+}
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.add_systems(Update, quick_save.run_if(resource_exists::<Map>()));
+}
+
+fn quick_save(mut cmd: Commands, actions: Res<Input<Action>>) {
+	if actions.just_pressed(Action::QuickSave) {
+		cmd.add(|world: &mut World| save_and_report(world, SaveSlot::Quick));
+	}
+	if actions.just_pressed(Action::QuickLoad) {
+		cmd.add(|world: &mut World| load_and_report(world, SaveSlot::Quick));
+	}
+}
@@ -0,0 +1,152 @@
+//! Player settings, shipped in `assets/settings.ron` and changed on the
+//! settings screen of the menus, which saves them to the [`UserConfig`]
+//! directory. Key bindings are saved separately, see
+//! [`Bindings`](crate::input::Bindings).
+
+use std::env;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowMode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::map::load_ron_blocking;
+use crate::AResult;
+
+/// Where the shipped [`Settings`] are loaded from, relative to the assets
+/// directory. The player's are saved under the same name in the
+/// [`UserConfig`] directory.
+const settingsPath: &str = "settings.ron";
+
+/// Where the player's overrides of shipped configuration, such as rebound
+/// keys, and their save games are kept. Defaults to an `undercity` directory
+/// in the platform's configuration directory; without one, nothing is saved.
+#[derive(Clone, Debug, Resource)]
+pub struct UserConfig {
+	pub dir: Option<PathBuf>,
+}
+
+impl Default for UserConfig {
+	fn default() -> Self {
+		let base = if cfg!(windows) {
+			env::var_os("APPDATA").map(PathBuf::from)
+		} else {
+			env::var_os("XDG_CONFIG_HOME")
+				.map(PathBuf::from)
+				.or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+		};
+		Self {
+			dir: base.map(|base| base.join("undercity")),
+		}
+	}
+}
+
+impl UserConfig {
+	/// Doesn't read or write any files, for tests.
+	pub fn none() -> Self {
+		Self { dir: None }
+	}
+
+	/// Reads the RON file `name` from the configuration directory, if there
+	/// is one.
+	pub fn load_blocking<T: DeserializeOwned>(&self, name: &str) -> AResult<Option<T>> {
+		let Some(path) = self.dir.as_ref().map(|dir| dir.join(name)) else {
+			return Ok(None);
+		};
+		if !path.exists() {
+			return Ok(None);
+		}
+		let str = std::fs::read_to_string(&path).with_context(|| format!("reading {path:?}"))?;
+		Ok(Some(ron::from_str(&str).with_context(|| format!("parsing {path:?}"))?))
+	}
+
+	/// Writes `value` to the RON file `name` in the configuration directory.
+	pub fn save_blocking<T: Serialize>(&self, name: &str, value: &T) -> AResult<()> {
+		let Some(dir) = &self.dir else {
+			return Ok(());
+		};
+		std::fs::create_dir_all(dir)?;
+		let str = ron::ser::to_string_pretty(value, default())?;
+		std::fs::write(dir.join(name), str)?;
+		Ok(())
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Resource, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+	/// Window size in logical pixels, when not fullscreen.
+	pub resolution: (f32, f32),
+	pub fullscreen: bool,
+	/// Master volume, from 0 to 1.
+	pub volume: f32,
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Self {
+			resolution: (1920.0, 1080.0),
+			fullscreen: false,
+			volume: 0.8,
+		}
+	}
+}
+
+impl Settings {
+	/// Window sizes offered on the settings screen.
+	pub const resolutions: &'static [(f32, f32)] = &[
+		(1280.0, 720.0),
+		(1600.0, 900.0),
+		(1920.0, 1080.0),
+		(2560.0, 1440.0),
+	];
+
+	/// Loads the player's settings, falling back to the shipped ones.
+	pub fn load_blocking(assets: &AssetServer, config: &UserConfig) -> AResult<Self> {
+		match config.load_blocking(settingsPath)? {
+			Some(settings) => Ok(settings),
+			None => load_ron_blocking(assets, settingsPath),
+		}
+	}
+
+	pub fn save_blocking(&self, config: &UserConfig) -> AResult<()> {
+		config.save_blocking(settingsPath, self)
+	}
+}
+
+#[linkme::distributed_slice(crate::setupApp)]
+fn setup_app(app: &mut App) {
+	app.init_resource::<UserConfig>();
+	app.add_systems(PreStartup, load_settings);
+	app.add_systems(Update, apply_window_settings);
+}
+
+fn load_settings(mut cmd: Commands, assets: Res<AssetServer>, config: Res<UserConfig>) {
+	let settings = Settings::load_blocking(&assets, &config).unwrap_or_else(|err| {
+		warn!("using default settings, could not load {settingsPath}: {err:#}");
+		default()
+	});
+	cmd.insert_resource(settings);
+}
+
+fn apply_window_settings(
+	settings: Option<Res<Settings>>,
+	mut window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+	let Some(settings) = settings.filter(|settings| settings.is_changed()) else {
+		return;
+	};
+	let Ok(mut window) = window.get_single_mut() else {
+		return;
+	};
+
+	let (width, height) = settings.resolution;
+	window.resolution.set(width, height);
+	window.mode = if settings.fullscreen {
+		WindowMode::BorderlessFullscreen
+	} else {
+		WindowMode::Windowed
+	};
+}
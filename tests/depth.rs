@@ -0,0 +1,86 @@
+//! Depth sorting of [`IsoSprite`]s for known arrangements of tiles and actors.
+//! Larger depths are drawn in front.
+
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use bevy::math::{uvec2, vec2, UVec2};
+use undercity::map::tileRadius;
+use undercity::{IsoSprite, RenderLayer};
+
+fn sprite(layer: RenderLayer, footprint: UVec2) -> IsoSprite {
+	IsoSprite {
+		layer,
+		footprint,
+		..Default::default()
+	}
+}
+
+/// Depth of `sprite` standing on the tile at `(x, y)`. North is `-y`, east is
+/// `+x`.
+fn depth(sprite: &IsoSprite, (x, y): (i32, i32)) -> f32 {
+	let pos = vec2(x as f32, y as f32) * tileRadius;
+	sprite.layer.depth(sprite.sort_point(pos))
+}
+
+#[test]
+fn player_and_wall() {
+	let wall = sprite(RenderLayer::Object, UVec2::ONE);
+	let player = sprite(RenderLayer::Object, UVec2::ONE);
+	let wallDepth = depth(&wall, (0, 0));
+
+	assert!(depth(&player, (0, -1)) < wallDepth, "player north of a wall is behind it");
+	assert!(depth(&player, (0, 1)) > wallDepth, "player south of a wall is in front of it");
+	assert!(depth(&player, (1, 0)) < wallDepth, "player east of a wall is behind it");
+	assert!(depth(&player, (-1, 0)) > wallDepth, "player west of a wall is in front of it");
+	assert!(depth(&player, (-1, 1)) > wallDepth, "player southwest of a wall is in front of it");
+	assert!(depth(&player, (1, -1)) < wallDepth, "player northeast of a wall is behind it");
+}
+
+#[test]
+fn player_beside_large_landmark() {
+	// covers (0, 0) and (1, 0)
+	let landmark = sprite(RenderLayer::Object, uvec2(2, 1));
+	let player = sprite(RenderLayer::Object, UVec2::ONE);
+	let landmarkDepth = depth(&landmark, (0, 0));
+
+	// south of the east half, on the diagonal of the origin
+	assert!(depth(&player, (1, 1)) > landmarkDepth);
+	// north of the west half, on the diagonal of the east half
+	assert!(depth(&player, (0, -1)) < landmarkDepth);
+	assert!(depth(&player, (2, 0)) < landmarkDepth);
+	assert!(depth(&player, (-1, 0)) > landmarkDepth);
+	assert!(depth(&player, (2, -1)) < landmarkDepth);
+	assert!(depth(&player, (-1, 1)) > landmarkDepth);
+
+	// covers (0, 0) and (0, 1)
+	let landmark = sprite(RenderLayer::Object, uvec2(1, 2));
+	let landmarkDepth = depth(&landmark, (0, 0));
+	assert!(depth(&player, (-1, 0)) > landmarkDepth);
+	assert!(depth(&player, (1, 1)) < landmarkDepth);
+	assert!(depth(&player, (0, 2)) > landmarkDepth);
+	assert!(depth(&player, (0, -1)) < landmarkDepth);
+}
+
+#[test]
+fn floor_decal_and_actor() {
+	let floor = sprite(RenderLayer::Floor, UVec2::ONE);
+	let trap = sprite(RenderLayer::FloorDecal, UVec2::ONE);
+	let player = sprite(RenderLayer::Object, UVec2::ONE);
+
+	assert!(depth(&trap, (0, 0)) > depth(&floor, (0, 0)));
+	// decals never cover actors, even ones further up the screen
+	assert!(depth(&player, (0, 0)) > depth(&trap, (0, 0)));
+	assert!(depth(&player, (5, -5)) > depth(&trap, (-5, 5)));
+	assert!(depth(&trap, (5, -5)) > depth(&floor, (-5, 5)));
+}
+
+#[test]
+fn cursor_and_wall() {
+	let wall = sprite(RenderLayer::Object, UVec2::ONE);
+	let cursor = sprite(RenderLayer::WorldUi, UVec2::ONE);
+
+	assert!(depth(&cursor, (0, 0)) > depth(&wall, (0, 0)));
+	// the cursor shows even behind a wall
+	assert!(depth(&cursor, (0, -1)) > depth(&wall, (0, 0)));
+	assert!(depth(&cursor, (5, -5)) > depth(&wall, (-5, 5)));
+}
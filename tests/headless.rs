@@ -0,0 +1,323 @@
+//! Gameplay tests, running the game headless under `MinimalPlugins`.
+
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bevy::math::{vec2, Vec3Swizzles};
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use rand::Rng;
+use undercity::entities::door::Door;
+use undercity::entities::health::{DamageEvent, DamageType, Health};
+use undercity::entities::player::{Cursor, Player, WalkPath};
+use undercity::entities::shrine::{Shrine, ShrineType};
+use undercity::entities::status::{StatusEffects, StatusKind};
+use undercity::map::{tileRadius, Map, Prefab, TilePos};
+use undercity::menu::GameState;
+use undercity::rng::GameRng;
+use undercity::save::{load_game, save_game, saveVersion, SaveMeta, SaveSlot};
+use undercity::settings::UserConfig;
+use undercity::{GamePlugin, HeadlessPlugin, LevelInfo};
+
+/// A room with the player spawn in the bottom row, a shrine of each type
+/// above it, and a door in the east wall leading outside.
+const room: &str = r#"(
+	key: {
+		'w': (
+			foreground: (ty: Wall(Solid), tileset: BrickCyan),
+			background: (ty: Floor(Tileset), tileset: BrickCyan),
+		),
+		'f': (
+			background: (ty: Floor(Tileset), tileset: BrickCyan),
+		),
+		's': (
+			foreground: (ty: Landmark(ty: SpawnPlayer)),
+			background: (ty: Floor(Tileset), tileset: BrickCyan),
+		),
+		'H': (
+			foreground: (ty: Landmark(ty: ShrineIdol)),
+			background: (ty: Floor(Tileset), tileset: BrickCyan),
+		),
+		'P': (
+			foreground: (ty: Landmark(ty: ShrineSkulls)),
+			background: (ty: Floor(Tileset), tileset: BrickCyan),
+		),
+		'B': (
+			foreground: (ty: Landmark(ty: ShrineScroll)),
+			background: (ty: Floor(Tileset), tileset: BrickCyan),
+		),
+		'D': (
+			foreground: (ty: DoorNS(), tileset: BrickCyan),
+			background: (ty: Floor(Tileset), tileset: BrickCyan),
+		),
+	},
+	map: [
+		"wwwwwww  ",
+		"wHfPfBw  ",
+		"wfsfffDff",
+		"wwwwwww  ",
+	],
+)"#;
+
+/// Starts the game in [`room`], ready to play.
+fn start() -> App {
+	let mut app = App::new();
+	app.add_plugins((MinimalPlugins, HeadlessPlugin, GamePlugin));
+	app.finish();
+	app.cleanup();
+	app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+		1.0 / 60.0,
+	)));
+	app.update();
+
+	let map = ron::from_str::<Prefab>(room).unwrap().into_map(Some(0)).unwrap();
+	undercity::load_level(&mut app.world, LevelInfo { level: 0, seed: 0 }, map);
+	app.world.resource_mut::<NextState<GameState>>().set(GameState::Playing);
+	// lets physics pick up the level's colliders
+	run(&mut app, 3);
+	app
+}
+
+fn run(app: &mut App, frames: usize) {
+	for _ in 0 .. frames {
+		app.update();
+	}
+}
+
+/// Holds `key` down for `frames` frames.
+fn hold(app: &mut App, key: KeyCode, frames: usize) {
+	app.world.resource_mut::<Input<KeyCode>>().press(key);
+	run(app, frames);
+	app.world.resource_mut::<Input<KeyCode>>().release(key);
+	app.update();
+}
+
+fn player(app: &mut App) -> Entity {
+	let mut query = app.world.query_filtered::<Entity, With<Player>>();
+	query.single(&app.world)
+}
+
+fn player_pos(app: &mut App) -> Vec2 {
+	let player = player(app);
+	app.world.get::<Transform>(player).unwrap().translation.xy()
+}
+
+fn find_door(app: &mut App) -> Entity {
+	let mut query = app.world.query_filtered::<Entity, With<Door>>();
+	query.single(&app.world)
+}
+
+fn is_open(app: &App, door: Entity) -> bool {
+	app.world.get::<Door>(door).unwrap().is_open()
+}
+
+fn find_shrine(app: &mut App, ty: ShrineType) -> Entity {
+	let mut query = app.world.query::<(Entity, &Shrine)>();
+	query
+		.iter(&app.world)
+		.find(|(_, shrine)| shrine.ty == ty)
+		.map(|(ent, _)| ent)
+		.unwrap()
+}
+
+/// Stands the player `offset` tiles from `target`, points the cursor at it and
+/// presses the interact key.
+fn interact(app: &mut App, target: Entity, offset: Vec2) {
+	let targetPos = app.world.get::<Transform>(target).unwrap().translation.xy();
+	let player = player(app);
+	app.world.get_mut::<Transform>(player).unwrap().translation =
+		(targetPos + offset * tileRadius, 0.0).into();
+	let mut cursor = app.world.query_filtered::<&mut Transform, With<Cursor>>();
+	cursor.single_mut(&mut app.world).translation = (targetPos, 0.0).into();
+	run(app, 2);
+	hold(app, KeyCode::E, 1);
+	run(app, 2);
+}
+
+/// Points the cursor at the tile `pos` and clicks.
+fn click_tile(app: &mut App, pos: TilePos) {
+	let mut cursor = app.world.query_filtered::<&mut Transform, With<Cursor>>();
+	cursor.single_mut(&mut app.world).translation = (pos.as_vec2() * tileRadius, 0.0).into();
+	run(app, 2);
+	app.world.resource_mut::<Input<MouseButton>>().press(MouseButton::Left);
+	app.update();
+	app.world.resource_mut::<Input<MouseButton>>().release(MouseButton::Left);
+	app.update();
+}
+
+fn health(app: &mut App) -> f32 {
+	let player = player(app);
+	app.world.get::<Health>(player).unwrap().current()
+}
+
+fn has_effect(app: &mut App, kind: StatusKind) -> bool {
+	let player = player(app);
+	app.world.get::<StatusEffects>(player).unwrap().has(kind)
+}
+
+/// Points [`UserConfig`] at an empty scratch directory for saves, returning
+/// it.
+fn use_save_dir(app: &mut App, name: &str) -> PathBuf {
+	let dir = std::env::temp_dir().join(format!("undercity-{}-{name}", std::process::id()));
+	let _ = fs::remove_dir_all(&dir);
+	app.insert_resource(UserConfig {
+		dir: Some(dir.clone()),
+	});
+	dir
+}
+
+fn teleport(app: &mut App, tile: Vec2) {
+	let player = player(app);
+	app.world.get_mut::<Transform>(player).unwrap().translation = (tile * tileRadius, 0.0).into();
+	run(app, 2);
+}
+
+#[test]
+fn spawns_player() {
+	let mut app = start();
+	assert_eq!(player_pos(&mut app), vec2(2.0, 2.0) * tileRadius);
+}
+
+#[test]
+fn walks() {
+	let mut app = start();
+	let before = player_pos(&mut app);
+	hold(&mut app, KeyCode::D, 20);
+	let after = player_pos(&mut app);
+	assert!(after.x > before.x, "player didn't walk east: {before} -> {after}");
+	assert!((after.y - before.y).abs() < 1.0);
+}
+
+#[test]
+fn toggles_door() {
+	let mut app = start();
+	let door = find_door(&mut app);
+	assert!(!is_open(&app, door));
+
+	interact(&mut app, door, vec2(-1.0, 0.0));
+	assert!(is_open(&app, door));
+	interact(&mut app, door, vec2(-1.0, 0.0));
+	assert!(!is_open(&app, door));
+}
+
+#[test]
+fn paths_through_open_doors_only() {
+	let mut app = start();
+	let door = find_door(&mut app);
+	let outside = TilePos::of(8, 2);
+
+	click_tile(&mut app, outside);
+	let player = player(&mut app);
+	assert!(app.world.get::<WalkPath>(player).is_none(), "walked into a closed door");
+
+	interact(&mut app, door, vec2(-1.0, 0.0));
+	click_tile(&mut app, outside);
+	let path = app.world.get::<WalkPath>(player).expect("no path through the open door");
+	assert!(path.tiles.contains(&TilePos::of(6, 2)));
+	assert_eq!(path.tiles.back(), Some(&outside));
+}
+
+#[test]
+fn heal_shrine() {
+	let mut app = start();
+	let player = player(&mut app);
+	app.world.send_event(DamageEvent {
+		target: player,
+		source: None,
+		amount: 50.0,
+		ty: DamageType::Physical,
+	});
+	run(&mut app, 2);
+	let before = health(&mut app);
+
+	let shrine = find_shrine(&mut app, ShrineType::Heal);
+	interact(&mut app, shrine, vec2(0.0, 1.0));
+	assert!(health(&mut app) > before);
+	assert!(has_effect(&mut app, StatusKind::Regeneration));
+	assert_eq!(app.world.get::<Shrine>(shrine).unwrap().uses, 1);
+}
+
+#[test]
+fn damage_shrine() {
+	let mut app = start();
+	let before = health(&mut app);
+
+	let shrine = find_shrine(&mut app, ShrineType::Damage);
+	interact(&mut app, shrine, vec2(0.0, 1.0));
+	assert!(health(&mut app) < before);
+	assert!(has_effect(&mut app, StatusKind::Poison));
+	assert_eq!(app.world.get::<Shrine>(shrine).unwrap().uses, 1);
+}
+
+#[test]
+fn blink_shrine() {
+	let mut app = start();
+	let shrine = find_shrine(&mut app, ShrineType::Blink);
+	let shrinePos = app.world.get::<Transform>(shrine).unwrap().translation.xy();
+	let before = shrinePos + vec2(0.0, 1.0) * tileRadius;
+
+	interact(&mut app, shrine, vec2(0.0, 1.0));
+	let after = player_pos(&mut app);
+	assert!(after.distance(before) >= tileRadius, "player didn't blink: {before} -> {after}");
+	let tile = (after / tileRadius).round();
+	let tile = app.world.resource::<Map>()[TilePos::of(tile.x as _, tile.y as _)];
+	assert!(tile.is_floor() && tile.is_walkable(), "player blinked onto {tile:?}");
+	assert!(has_effect(&mut app, StatusKind::Haste));
+	assert_eq!(app.world.get::<Shrine>(shrine).unwrap().uses, 1);
+}
+
+#[test]
+fn save_round_trip() {
+	let mut app = start();
+	let dir = use_save_dir(&mut app, "round_trip");
+	let door = find_door(&mut app);
+	let shrine = find_shrine(&mut app, ShrineType::Damage);
+	interact(&mut app, door, vec2(-1.0, 0.0));
+	interact(&mut app, shrine, vec2(0.0, 1.0));
+	teleport(&mut app, vec2(4.0, 2.0));
+
+	let savedPos = player_pos(&mut app);
+	let savedHealth = health(&mut app);
+	save_game(&mut app.world, SaveSlot::Quick).unwrap();
+	let nextRoll: u64 = app.world.resource::<GameRng>().clone().gen();
+
+	// change everything that was saved
+	interact(&mut app, door, vec2(-1.0, 0.0));
+	interact(&mut app, shrine, vec2(0.0, 1.0));
+	teleport(&mut app, vec2(2.0, 1.0));
+	app.world.resource_mut::<GameRng>().gen::<u64>();
+	assert!(!is_open(&app, door));
+	assert!(health(&mut app) < savedHealth);
+
+	load_game(&mut app.world, SaveSlot::Quick).unwrap();
+	assert_eq!(player_pos(&mut app), savedPos);
+	assert_eq!(health(&mut app), savedHealth);
+	// doors and shrines are respawned from the map
+	let door = find_door(&mut app);
+	assert!(is_open(&app, door));
+	let shrine = find_shrine(&mut app, ShrineType::Damage);
+	assert_eq!(app.world.get::<Shrine>(shrine).unwrap().uses, 1);
+	assert_eq!(app.world.resource_mut::<GameRng>().gen::<u64>(), nextRoll);
+	fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn refuses_other_save_versions() {
+	let mut app = start();
+	let dir = use_save_dir(&mut app, "versions");
+	save_game(&mut app.world, SaveSlot::Quick).unwrap();
+	let metaPath = dir.join("saves/quick/meta.ron");
+	let mut meta: SaveMeta = ron::from_str(&fs::read_to_string(&metaPath).unwrap()).unwrap();
+	meta.version = saveVersion + 1;
+	fs::write(&metaPath, ron::to_string(&meta).unwrap()).unwrap();
+
+	teleport(&mut app, vec2(4.0, 2.0));
+	let before = player_pos(&mut app);
+	assert!(load_game(&mut app.world, SaveSlot::Quick).is_err());
+	// the game goes on untouched
+	assert_eq!(player_pos(&mut app), before);
+	fs::remove_dir_all(dir).unwrap();
+}
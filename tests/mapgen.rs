@@ -0,0 +1,113 @@
+//! Map generation tests: every level of `levels.ron` must generate, and be
+//! fully connected, for any seed.
+
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use std::collections::HashSet;
+
+use bevy::asset::{AssetServer, FileAssetIo};
+use undercity::map::gen::connectivity::{ensure_connected, walkable_regions};
+use undercity::map::gen::{generate_level, LevelDef};
+use undercity::map::{load_ron_blocking, FloorType, Map, MutMap, Prefab, Tile, TileType, Tileset};
+
+/// Seeds tried per level.
+const seeds: u64 = 1000;
+
+fn asset_server() -> AssetServer {
+	AssetServer::new(FileAssetIo::new("assets", &None))
+}
+
+/// Asserts that all walkable tiles of `map` form a single region, and that
+/// every spawnpoint lies in it.
+fn assert_connected(map: &Map, context: &str) {
+	let regions = walkable_regions(map);
+	assert_eq!(regions.len(), 1, "{context}: {} walkable regions", regions.len());
+	let region: HashSet<_> = regions[0].iter().copied().collect();
+
+	let spawns: Vec<_> = map
+		.used_tiles()
+		.tiles()
+		.filter(|&pos| {
+			matches!(map[pos].foreground.ty, TileType::Landmark { ty, .. } if ty.is_spawn())
+		})
+		.collect();
+	assert!(!spawns.is_empty(), "{context}: no spawnpoints");
+	for pos in spawns {
+		assert!(region.contains(&pos), "{context}: spawnpoint at {pos:?} is unreachable");
+	}
+}
+
+#[test]
+fn levels_generate_connected() {
+	let assets = asset_server();
+	let levels: Vec<LevelDef> = load_ron_blocking(&assets, "levels.ron").unwrap();
+	for level in 0 .. levels.len() {
+		for seed in 0 .. seeds {
+			let context = format!("level {level} with seed {seed}");
+			let map = generate_level(&assets, level, seed)
+				.unwrap_or_else(|err| panic!("{context}: {err:#}"));
+			assert_connected(&map, &context);
+		}
+	}
+}
+
+/// Parses a prefab with walls `w`, floor `f` and shrines `S`, the latter
+/// being obstacles.
+fn prefab_map(rows: &[&str]) -> MutMap {
+	let rows: Vec<_> = rows.iter().map(|row| format!("{row:?}")).collect();
+	let ron = format!(
+		r#"(
+			key: {{
+				'w': (
+					foreground: (ty: Wall(Solid), tileset: BrickCyan),
+					background: (ty: Floor(Tileset), tileset: BrickCyan),
+				),
+				'f': (
+					background: (ty: Floor(Tileset), tileset: BrickCyan),
+				),
+				'S': (
+					foreground: (ty: Landmark(ty: ShrineIdol)),
+					background: (ty: Floor(Tileset), tileset: BrickCyan),
+				),
+			}},
+			map: [{}],
+		)"#,
+		rows.join(", ")
+	);
+	ron::from_str::<Prefab>(&ron).unwrap().into_map(Some(0)).unwrap()
+}
+
+fn floor() -> Tile {
+	Tile {
+		ty: TileType::Floor(FloorType::Tileset),
+		tileset: Tileset::BrickCyan,
+	}
+}
+
+#[test]
+fn connects_separate_rooms() {
+	let mut map = prefab_map(&[
+		"wwww   wwww",
+		"wffw   wffw",
+		"wffw   wffw",
+		"wwww   wwww",
+	]);
+	assert_eq!(walkable_regions(&map).len(), 2);
+
+	ensure_connected(&mut map, floor()).unwrap();
+	assert_eq!(walkable_regions(&map).len(), 1);
+}
+
+#[test]
+fn fails_on_enclosed_room() {
+	// obstacles can't be cut through, so the middle tile stays isolated
+	let mut map = prefab_map(&[
+		"wwwwwwwww",
+		"wffffwSww",
+		"wffffSfSw",
+		"wffffwSww",
+		"wwwwwwwww",
+	]);
+	assert_eq!(walkable_regions(&map).len(), 2);
+	assert!(ensure_connected(&mut map, floor()).is_err());
+}